@@ -1,31 +1,129 @@
 use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    env,
+    fmt::{self, Debug, Display},
     io::{self, stdin},
     str::FromStr,
 };
 
 fn main() -> io::Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
     let input = io::read_to_string(stdin())?;
-    match part_1(&input) {
+    let result = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => report(&input, 1),
+        ["overlapping", sections] => query_overlapping(&input, sections),
+        ["pairs"] => query_pairs(&input),
+        ["check"] => check(&input),
+        [over] => match over.parse() {
+            Ok(over) => report(&input, over),
+            Err(err) => Err(err.into()),
+        },
+        _ => Err("usage: day4 [K | overlapping <start>-<end> | pairs | check] < input".into()),
+    };
+    if let Err(err) = result {
+        eprintln!("ERROR {}", err);
+    }
+    Ok(())
+}
+
+fn report(input: &str, over: usize) -> Result<(), Box<dyn std::error::Error>> {
+    match part_1(input) {
         Ok(result) => println!("Part 1: {}", result),
         Err(err) => eprintln!("Part 1: ERROR {}", err),
     }
-    match part_2(&input) {
+    match part_2(input) {
         Ok(result) => println!("Part 2: {}", result),
         Err(err) => eprintln!("Part 2: ERROR {}", err),
     }
+    let roster: Roster<u64> = input.parse()?;
+    println!("Uncovered: {}", join(&roster.uncovered()));
+    println!(
+        "Covered by more than {}: {}",
+        over,
+        join(&roster.covered_by_more_than(over))
+    );
+    if let Some((section, elves)) = roster.most_assigned() {
+        println!("Most assigned: section {} ({} elves)", section, elves);
+    }
+    Ok(())
+}
+
+fn query_overlapping(input: &str, sections: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let sections: Assignment<u64> = sections.parse()?;
+    let roster: Roster<u64> = input.parse()?;
+    let index = AssignmentIndex::from(&roster);
+    for (elf, assignment) in index.overlapping(&sections) {
+        println!("{}: {}", elf, assignment);
+    }
+    Ok(())
+}
+
+fn query_pairs(input: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let roster: Roster<u64> = input.parse()?;
+    let index = AssignmentIndex::from(&roster);
+    let pairs = index.overlapping_pairs();
+    for (first, second) in &pairs {
+        println!("{} & {}", first, second);
+    }
+    println!("Overlapping pairs: {}", pairs.len());
+    Ok(())
+}
+
+fn check(input: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let roster: Roster<u64> = input.parse()?;
+    println!(
+        "OK: {} lines, {} assignments",
+        roster.groups.len(),
+        roster.assignments().count()
+    );
     Ok(())
 }
 
+fn join<T: Section>(assignments: &[Assignment<T>]) -> String {
+    assignments
+        .iter()
+        .map(Assignment::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+trait Section: Copy + Ord + FromStr + Display + Debug {
+    const MAX: Self;
+    fn next(self) -> Option<Self>;
+    fn previous(self) -> Option<Self>;
+}
+
+impl Section for u32 {
+    const MAX: Self = u32::MAX;
+    fn next(self) -> Option<Self> {
+        self.checked_add(1)
+    }
+    fn previous(self) -> Option<Self> {
+        self.checked_sub(1)
+    }
+}
+
+impl Section for u64 {
+    const MAX: Self = u64::MAX;
+    fn next(self) -> Option<Self> {
+        self.checked_add(1)
+    }
+    fn previous(self) -> Option<Self> {
+        self.checked_sub(1)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
-enum ParseError {
-    SectionOrderInvalid { start: u32, end: u32 },
+enum ParseError<T = u32> {
+    SectionOrderInvalid { start: T, end: T },
     SectionRangeMissing,
     SectionNumberInvalid,
     AssignmentMissing,
 }
 
-impl std::fmt::Display for ParseError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl<T: Display> Display for ParseError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let msg = match self {
             ParseError::SectionOrderInvalid {
                 start: first,
@@ -39,23 +137,58 @@ impl std::fmt::Display for ParseError {
             }
             ParseError::SectionNumberInvalid => "section is not a valid number".to_string(),
             ParseError::AssignmentMissing => {
-                "must supply two assignments, seperated by ','".to_string()
+                "must supply at least one assignment, seperated by ','".to_string()
             }
         };
         write!(f, "parse failure: {}", msg)
     }
 }
 
-impl std::error::Error for ParseError {}
+impl<T: Display + Debug> std::error::Error for ParseError<T> {}
 
-#[derive(Debug, PartialEq)]
-struct Assignment {
-    start: u32,
-    end: u32,
+#[derive(Debug, PartialEq, Eq)]
+struct LineError<T = u32> {
+    line: usize,
+    text: String,
+    error: ParseError<T>,
+}
+
+impl<T: Display> Display for LineError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {} ({:?})", self.line, self.error, self.text)
+    }
+}
+
+impl<T: Display + Debug> std::error::Error for LineError<T> {}
+
+#[derive(Debug, PartialEq, Eq)]
+struct LineErrors<T = u32>(Vec<LineError<T>>);
+
+impl<T: Display> Display for LineErrors<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<String> = self.0.iter().map(LineError::to_string).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl<T: Display + Debug> std::error::Error for LineErrors<T> {}
+
+fn parse_line<T: Section>(index: usize, line: &str) -> Result<AssignmentGroup<T>, LineError<T>> {
+    line.parse().map_err(|error| LineError {
+        line: index + 1,
+        text: line.to_string(),
+        error,
+    })
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Assignment<T = u32> {
+    start: T,
+    end: T,
 }
 
-impl FromStr for Assignment {
-    type Err = ParseError;
+impl<T: Section> FromStr for Assignment<T> {
+    type Err = ParseError<T>;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let (start, end) = s.split_once('-').ok_or(ParseError::SectionRangeMissing)?;
         let start = start.parse().or(Err(ParseError::SectionNumberInvalid))?;
@@ -67,47 +200,311 @@ impl FromStr for Assignment {
     }
 }
 
-impl Assignment {
-    fn contains(&self, other: &Assignment) -> bool {
+impl<T: Display> Display for Assignment<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+impl<T: PartialOrd> Assignment<T> {
+    fn contains(&self, other: &Assignment<T>) -> bool {
         self.start <= other.start && self.end >= other.end
     }
 
-    fn overlaps(&self, other: &Assignment) -> bool {
+    fn overlaps(&self, other: &Assignment<T>) -> bool {
         (self.start <= other.start && self.end >= other.start)
             || (other.start <= self.start && other.end >= self.start)
     }
 }
 
 #[derive(Debug, PartialEq)]
-struct AssignmentPair {
-    first: Assignment,
-    second: Assignment,
+struct AssignmentGroup<T = u32> {
+    assignments: Vec<Assignment<T>>,
+}
+
+impl<T: Section> FromStr for AssignmentGroup<T> {
+    type Err = ParseError<T>;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err(ParseError::AssignmentMissing);
+        }
+        let assignments = s.split(',').map(str::parse).collect::<Result<_, _>>()?;
+        Ok(AssignmentGroup { assignments })
+    }
+}
+
+impl<T: Section> AssignmentGroup<T> {
+    // Indices of elves whose sections are fully covered by another elf on the same line
+    fn redundant(&self) -> Vec<usize> {
+        self.assignments
+            .iter()
+            .enumerate()
+            .filter(|(i, assignment)| {
+                self.assignments
+                    .iter()
+                    .enumerate()
+                    .any(|(j, other)| *i != j && other.contains(assignment))
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    fn has_overlap(&self) -> bool {
+        self.assignments.iter().enumerate().any(|(i, assignment)| {
+            self.assignments[i + 1..]
+                .iter()
+                .any(|other| assignment.overlaps(other))
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Coverage<T> {
+    sections: Assignment<T>,
+    elves: usize,
 }
 
-impl FromStr for AssignmentPair {
-    type Err = ParseError;
+#[derive(Debug, PartialEq)]
+struct Roster<T = u32> {
+    groups: Vec<AssignmentGroup<T>>,
+}
+
+impl<T: Section> FromStr for Roster<T> {
+    type Err = LineErrors<T>;
+    // Keeps going past bad lines so every one of them is reported together
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (first, second) = s.split_once(',').ok_or(ParseError::AssignmentMissing)?;
-        let first = first.parse()?;
-        let second = second.parse()?;
-        Ok(AssignmentPair { first, second })
+        let mut groups = vec![];
+        let mut errors = vec![];
+        for (index, line) in s.lines().enumerate() {
+            match parse_line(index, line) {
+                Ok(group) => groups.push(group),
+                Err(err) => errors.push(err),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(LineErrors(errors));
+        }
+        Ok(Roster { groups })
+    }
+}
+
+impl<T: Section> Roster<T> {
+    fn assignments(&self) -> impl Iterator<Item = &Assignment<T>> {
+        self.groups
+            .iter()
+            .flat_map(|group| group.assignments.iter())
+    }
+
+    // Sweep line over start/end events, producing runs of sections that share the
+    // same number of assigned elves from the lowest assigned section to the highest
+    fn coverage(&self) -> Vec<Coverage<T>> {
+        let mut events: Vec<(T, bool)> = self
+            .assignments()
+            .flat_map(|a| {
+                [Some((a.start, true)), a.end.next().map(|e| (e, false))]
+                    .into_iter()
+                    .flatten()
+            })
+            .collect();
+        events.sort_unstable();
+
+        let mut coverage = vec![];
+        let mut elves = 0;
+        let mut previous: Option<T> = None;
+        let mut events = events.into_iter().peekable();
+        while let Some(&(position, _)) = events.peek() {
+            if let (Some(start), Some(end)) = (previous, position.previous()) {
+                coverage.push(Coverage {
+                    sections: Assignment { start, end },
+                    elves,
+                });
+            }
+            let (mut starts, mut ends) = (0, 0);
+            while let Some((_, is_start)) = events.next_if(|(p, _)| *p == position) {
+                if is_start {
+                    starts += 1;
+                } else {
+                    ends += 1;
+                }
+            }
+            elves = elves + starts - ends;
+            previous = Some(position);
+        }
+        if let (Some(start), true) = (previous, elves > 0) {
+            coverage.push(Coverage {
+                sections: Assignment { start, end: T::MAX },
+                elves,
+            });
+        }
+        coverage
+    }
+
+    fn sections_where(&self, predicate: impl Fn(usize) -> bool) -> Vec<Assignment<T>> {
+        let mut sections: Vec<Assignment<T>> = vec![];
+        for Coverage {
+            sections: run,
+            elves,
+        } in self.coverage()
+        {
+            if !predicate(elves) {
+                continue;
+            }
+            match sections.last_mut() {
+                Some(last) if last.end.next() == Some(run.start) => last.end = run.end,
+                _ => sections.push(run),
+            }
+        }
+        sections
+    }
+
+    fn uncovered(&self) -> Vec<Assignment<T>> {
+        self.sections_where(|elves| elves == 0)
+    }
+
+    fn covered_by_more_than(&self, k: usize) -> Vec<Assignment<T>> {
+        self.sections_where(|elves| elves > k)
+    }
+
+    fn most_assigned(&self) -> Option<(T, usize)> {
+        self.coverage()
+            .into_iter()
+            .rev()
+            .max_by_key(|c| c.elves)
+            .map(|c| (c.sections.start, c.elves))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+struct Elf {
+    line: usize,
+    position: usize,
+}
+
+impl Display for Elf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {} elf {}", self.line + 1, self.position + 1)
+    }
+}
+
+// Assignments sorted by start, laid out as an implicit balanced tree where the
+// midpoint of every range is the subtree root, augmented with the maximum end
+// found in each subtree so whole branches can be skipped while querying.
+#[derive(Debug)]
+struct AssignmentIndex<T = u32> {
+    entries: Vec<(Elf, Assignment<T>)>,
+    max_end: Vec<T>,
+}
+
+impl<T: Section> From<&Roster<T>> for AssignmentIndex<T> {
+    fn from(roster: &Roster<T>) -> Self {
+        let mut entries: Vec<(Elf, Assignment<T>)> = roster
+            .groups
+            .iter()
+            .enumerate()
+            .flat_map(|(line, group)| {
+                group
+                    .assignments
+                    .iter()
+                    .enumerate()
+                    .map(move |(position, a)| (Elf { line, position }, a.clone()))
+            })
+            .collect();
+        entries.sort_unstable_by_key(|(elf, a)| (a.start, *elf));
+        let mut index = AssignmentIndex {
+            max_end: entries.iter().map(|(_, a)| a.end).collect(),
+            entries,
+        };
+        index.build(0, index.entries.len());
+        index
+    }
+}
+
+impl<T: Section> AssignmentIndex<T> {
+    fn build(&mut self, lo: usize, hi: usize) -> Option<T> {
+        if lo >= hi {
+            return None;
+        }
+        let mid = (lo + hi) / 2;
+        let max_end = [
+            self.build(lo, mid),
+            self.build(mid + 1, hi),
+            Some(self.entries[mid].1.end),
+        ]
+        .into_iter()
+        .flatten()
+        .max()?;
+        self.max_end[mid] = max_end;
+        Some(max_end)
+    }
+
+    fn overlapping(&self, sections: &Assignment<T>) -> Vec<&(Elf, Assignment<T>)> {
+        let mut found = vec![];
+        self.search(0, self.entries.len(), sections, &mut found);
+        found
+    }
+
+    fn search<'a>(
+        &'a self,
+        lo: usize,
+        hi: usize,
+        sections: &Assignment<T>,
+        found: &mut Vec<&'a (Elf, Assignment<T>)>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = (lo + hi) / 2;
+        if self.max_end[mid] < sections.start {
+            return;
+        }
+        self.search(lo, mid, sections, found);
+        let entry = &self.entries[mid];
+        if entry.1.overlaps(sections) {
+            found.push(entry);
+        }
+        if entry.1.start <= sections.end {
+            self.search(mid + 1, hi, sections, found);
+        }
+    }
+
+    // Sweep in start order keeping the assignments still open in a min-heap on
+    // their end, so every pair is found in O(n log n + pairs)
+    fn overlapping_pairs(&self) -> Vec<(Elf, Elf)> {
+        let mut pairs = vec![];
+        let mut active: BinaryHeap<Reverse<(T, Elf)>> = BinaryHeap::new();
+        for (elf, assignment) in &self.entries {
+            while active
+                .peek()
+                .is_some_and(|Reverse((end, _))| *end < assignment.start)
+            {
+                active.pop();
+            }
+            pairs.extend(
+                active
+                    .iter()
+                    .map(|Reverse((_, other))| (*other.min(elf), *other.max(elf))),
+            );
+            active.push(Reverse((assignment.end, *elf)));
+        }
+        pairs.sort_unstable();
+        pairs
     }
 }
 
 fn part_1(input: &str) -> Result<u32, Box<dyn std::error::Error>> {
     let mut count = 0;
-    for line in input.lines() {
-        let pair: AssignmentPair = line.parse()?;
-        count += (pair.first.contains(&pair.second) || pair.second.contains(&pair.first)) as u32
+    for (index, line) in input.lines().enumerate() {
+        let group: AssignmentGroup<u64> = parse_line(index, line)?;
+        count += (!group.redundant().is_empty()) as u32
     }
     Ok(count)
 }
 
 fn part_2(input: &str) -> Result<u32, Box<dyn std::error::Error>> {
     let mut count = 0;
-    for line in input.lines() {
-        let pair: AssignmentPair = line.parse()?;
-        count += (pair.first.overlaps(&pair.second)) as u32
+    for (index, line) in input.lines().enumerate() {
+        let group: AssignmentGroup<u64> = parse_line(index, line)?;
+        count += group.has_overlap() as u32
     }
     Ok(count)
 }
@@ -137,9 +534,12 @@ mod test {
 
     #[test]
     fn assignment_from_str() {
-        assert_eq!("2-4".parse(), Ok(Assignment { start: 2, end: 4 }));
         assert_eq!(
-            "10-100".parse(),
+            "2-4".parse::<Assignment>(),
+            Ok(Assignment { start: 2, end: 4 })
+        );
+        assert_eq!(
+            "10-100".parse::<Assignment>(),
             Ok(Assignment {
                 start: 10,
                 end: 100
@@ -160,32 +560,200 @@ mod test {
     }
 
     #[test]
-    fn assignment_pair_from_str() {
+    fn assignment_group_from_str() {
+        assert_eq!(
+            "2-4,6-8".parse::<AssignmentGroup>(),
+            Ok(AssignmentGroup {
+                assignments: vec![
+                    Assignment { start: 2, end: 4 },
+                    Assignment { start: 6, end: 8 }
+                ]
+            })
+        );
         assert_eq!(
-            "2-4,6-8".parse(),
-            Ok(AssignmentPair {
-                first: Assignment { start: 2, end: 4 },
-                second: Assignment { start: 6, end: 8 }
+            "10-100,6-8,1-2".parse::<AssignmentGroup>(),
+            Ok(AssignmentGroup {
+                assignments: vec![
+                    Assignment {
+                        start: 10,
+                        end: 100
+                    },
+                    Assignment { start: 6, end: 8 },
+                    Assignment { start: 1, end: 2 }
+                ]
             })
         );
         assert_eq!(
-            "10-100,6-8".parse(),
-            Ok(AssignmentPair {
-                first: Assignment {
+            "10-100".parse::<AssignmentGroup>(),
+            Ok(AssignmentGroup {
+                assignments: vec![Assignment {
                     start: 10,
                     end: 100
-                },
-                second: Assignment { start: 6, end: 8 }
+                }]
             })
         );
         assert_eq!(
-            "10-100".parse::<AssignmentPair>(),
+            "".parse::<AssignmentGroup>(),
             Err(ParseError::AssignmentMissing)
         );
         assert_eq!(
-            "10-100,a-b".parse::<AssignmentPair>(),
+            "10-100,a-b".parse::<AssignmentGroup>(),
+            Err(ParseError::SectionNumberInvalid)
+        );
+    }
+
+    #[test]
+    fn roster_reports_every_bad_line() {
+        assert_eq!(
+            "2-4,6-8\n4-2,1-1\n2-3,4-5\n1-a".parse::<Roster>(),
+            Err(LineErrors(vec![
+                LineError {
+                    line: 2,
+                    text: "4-2,1-1".to_string(),
+                    error: ParseError::SectionOrderInvalid { start: 4, end: 2 }
+                },
+                LineError {
+                    line: 4,
+                    text: "1-a".to_string(),
+                    error: ParseError::SectionNumberInvalid
+                }
+            ]))
+        );
+        assert_eq!(
+            part_1("2-4,6-8\n4-2,1-1").map_err(|err| err.to_string()),
+            Err("line 2: parse failure: section order is invalid, 4 cannot be before 2 (\"4-2,1-1\")".to_string())
+        );
+    }
+
+    #[test]
+    fn u64_sections() -> Result<(), Box<dyn std::error::Error>> {
+        let roster: Roster<u64> = "1-5000000000,4999999999-6000000000".parse()?;
+        assert_eq!(
+            roster.covered_by_more_than(1),
+            vec![Assignment {
+                start: 4_999_999_999,
+                end: 5_000_000_000
+            }]
+        );
+        assert_eq!(
+            "1-5000000000".parse::<Assignment>(),
             Err(ParseError::SectionNumberInvalid)
         );
+        assert_eq!(part_1("1-5000000000,2-4999999999")?, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_redundant() -> Result<(), ParseError> {
+        assert_eq!(
+            "2-8,3-7,1-2,4-4".parse::<AssignmentGroup>()?.redundant(),
+            vec![1, 3]
+        );
+        assert_eq!("2-4,6-8".parse::<AssignmentGroup>()?.redundant(), vec![]);
+        assert_eq!(
+            "3-3,3-3".parse::<AssignmentGroup>()?.redundant(),
+            vec![0, 1]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_roster_coverage() -> Result<(), Box<dyn std::error::Error>> {
+        let roster: Roster = EXAMPLE_INPUT.parse()?;
+        assert_eq!(roster.uncovered(), vec![]);
+        assert_eq!(
+            roster.covered_by_more_than(3),
+            vec![Assignment { start: 2, end: 8 }]
+        );
+        assert_eq!(
+            roster.covered_by_more_than(6),
+            vec![Assignment { start: 4, end: 6 }]
+        );
+        assert_eq!(roster.most_assigned(), Some((6, 8)));
+
+        let roster: Roster = "1-2,5-6\n9-9".parse()?;
+        assert_eq!(
+            roster.uncovered(),
+            vec![
+                Assignment { start: 3, end: 4 },
+                Assignment { start: 7, end: 8 }
+            ]
+        );
+        assert_eq!(roster.covered_by_more_than(1), vec![]);
+        assert_eq!(roster.most_assigned(), Some((1, 1)));
+
+        let roster: Roster = "1-4294967295,4294967295-4294967295".parse()?;
+        assert_eq!(
+            roster.covered_by_more_than(1),
+            vec![Assignment {
+                start: u32::MAX,
+                end: u32::MAX
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_overlapping() -> Result<(), Box<dyn std::error::Error>> {
+        let roster: Roster = EXAMPLE_INPUT.parse()?;
+        let index = AssignmentIndex::from(&roster);
+        let elves: Vec<Elf> = index
+            .overlapping(&Assignment { start: 9, end: 12 })
+            .into_iter()
+            .map(|(elf, _)| *elf)
+            .collect();
+        assert_eq!(
+            elves,
+            vec![Elf {
+                line: 2,
+                position: 1
+            }]
+        );
+
+        let query = Assignment { start: 3, end: 3 };
+        let mut elves: Vec<Elf> = index
+            .overlapping(&query)
+            .into_iter()
+            .map(|(elf, _)| *elf)
+            .collect();
+        elves.sort();
+        let expected: Vec<Elf> = index
+            .entries
+            .iter()
+            .filter(|(_, a)| a.overlaps(&query))
+            .map(|(elf, _)| *elf)
+            .collect::<std::collections::BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        assert_eq!(elves, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_overlapping_pairs() -> Result<(), Box<dyn std::error::Error>> {
+        let roster: Roster = EXAMPLE_INPUT.parse()?;
+        let index = AssignmentIndex::from(&roster);
+        let mut expected = vec![];
+        for (i, (first, a)) in index.entries.iter().enumerate() {
+            for (second, b) in &index.entries[i + 1..] {
+                if a.overlaps(b) {
+                    expected.push((*first.min(second), *first.max(second)));
+                }
+            }
+        }
+        expected.sort();
+        assert_eq!(index.overlapping_pairs(), expected);
+        assert!(index.overlapping_pairs().contains(&(
+            Elf {
+                line: 2,
+                position: 0
+            },
+            Elf {
+                line: 2,
+                position: 1
+            }
+        )));
+        Ok(())
     }
 
     #[test]
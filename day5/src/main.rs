@@ -1,87 +1,573 @@
 use core::fmt;
 use std::{
+    collections::{HashSet, VecDeque},
+    env,
     io::{self, stdin},
+    num::NonZeroUsize,
     vec,
 };
 
 use nom::{
-    branch::alt,
-    bytes::complete::tag,
-    character::complete::{anychar, char, digit1, multispace1, newline, space1},
+    bytes::complete::{is_not, tag},
+    character::complete::{char, digit1},
     combinator::{map, map_res},
-    multi::separated_list1,
     sequence::{delimited, preceded},
 };
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 enum Error {
-    ParsingError,
+    Parsing,
+    MalformedRow {
+        line: usize,
+        column: usize,
+        reason: &'static str,
+    },
+    UnknownCrane(String),
+    InvalidMove(MoveError),
+    NoPlan {
+        target: String,
+        depth: usize,
+        states: usize,
+    },
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            Error::MalformedRow {
+                line,
+                column,
+                reason,
+            } => write!(
+                f,
+                "MalformedRow line {} column {}: {}",
+                line, column, reason
+            ),
+            Error::UnknownCrane(model) => write!(f, "UnknownCrane {}", model),
+            Error::InvalidMove(err) => write!(f, "InvalidMove {}", err),
+            Error::NoPlan {
+                target,
+                depth,
+                states,
+            } => write!(
+                f,
+                "NoPlan reaching {} within {} moves and {} states",
+                target, depth, states
+            ),
+            _ => write!(f, "{:?}", self),
+        }
     }
 }
 
 fn main() -> io::Result<()> {
     let input = io::read_to_string(stdin())?;
-    match part1(&input) {
-        Ok(result) => println!("Part 1: {}", result),
-        Err(err) => eprintln!("Part 1: Error = {}", err),
+    let (flags, args): (Vec<String>, Vec<String>) =
+        env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    let mut execution = Execution::Checked;
+    let mut depth = 6;
+    let mut max_states = 100_000;
+    for flag in &flags {
+        match flag.split_once('=').unwrap_or((flag, "")) {
+            ("--lenient", "") => execution = Execution::Lenient,
+            ("--depth", value) => match value.parse() {
+                Ok(value) => depth = value,
+                Err(_) => {
+                    eprintln!("Error = {} is not a depth", value);
+                    return Ok(());
+                }
+            },
+            ("--max-states", value) => match value.parse() {
+                Ok(value) => max_states = value,
+                Err(_) => {
+                    eprintln!("Error = {} is not a number of states", value);
+                    return Ok(());
+                }
+            },
+            _ => {
+                eprintln!("Error = unknown option {}", flag);
+                return Ok(());
+            }
+        }
     }
-    match part2(&input) {
-        Ok(result) => println!("Part 2: {}", result),
-        Err(err) => eprintln!("Part 2: Error = {}", err),
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => {
+            match part1(&input) {
+                Ok(result) => println!("Part 1: {}", result),
+                Err(err) => eprintln!("Part 1: Error = {}", err),
+            }
+            match part2(&input) {
+                Ok(result) => println!("Part 2: {}", result),
+                Err(err) => eprintln!("Part 2: Error = {}", err),
+            }
+        }
+        ["steps"] => {
+            if let Err(err) = print_steps(&input, &CrateMover9000, execution) {
+                eprintln!("Steps: Error = {}", err);
+            }
+        }
+        ["steps", model] => {
+            if let Err(err) =
+                crane(model).and_then(|crane| print_steps(&input, crane.as_ref(), execution))
+            {
+                eprintln!("Steps: Error = {}", err);
+            }
+        }
+        ["plan", target] => {
+            if let Err(err) = print_plan(&input, target, &CrateMover9000, depth, max_states) {
+                eprintln!("Plan: Error = {}", err);
+            }
+        }
+        ["plan", target, model] => {
+            if let Err(err) = crane(model)
+                .and_then(|crane| print_plan(&input, target, crane.as_ref(), depth, max_states))
+            {
+                eprintln!("Plan: Error = {}", err);
+            }
+        }
+        [model] => match crane(model).and_then(|crane| run(&input, crane.as_ref(), execution)) {
+            Ok((result, adjusted)) => {
+                adjusted.iter().for_each(print_adjustment);
+                println!("Crane {}: {}", model, result)
+            }
+            Err(err) => eprintln!("Crane {}: Error = {}", model, err),
+        },
+        _ => eprintln!(
+            "usage: day5 [--lenient] [--depth=<n>] [--max-states=<n>] [steps | plan <tops>] [9000 | 9001 | capacity:<n> | bottom] < input"
+        ),
     }
     Ok(())
 }
 
+fn crane(model: &str) -> Result<Box<dyn CrateMover>, Error> {
+    match model.split_once(':') {
+        None if model == "9000" => Ok(Box::new(CrateMover9000)),
+        None if model == "9001" => Ok(Box::new(CrateMover9001)),
+        None if model == "bottom" => Ok(Box::new(BottomCrateMover)),
+        Some(("capacity", capacity)) => match capacity.parse() {
+            Ok(capacity) => Ok(Box::new(CapacityCrateMover { capacity })),
+            _ => Err(Error::UnknownCrane(model.to_string())),
+        },
+        _ => Err(Error::UnknownCrane(model.to_string())),
+    }
+}
+
 #[derive(Debug, PartialEq)]
+enum MoveError {
+    StackMissing {
+        index: usize,
+        stack: usize,
+    },
+    NotEnoughCrates {
+        index: usize,
+        stack: usize,
+        count: usize,
+        height: usize,
+    },
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::StackMissing { index, stack } => {
+                write!(f, "move #{}: stack {} does not exist", index + 1, stack)
+            }
+            MoveError::NotEnoughCrates {
+                index,
+                stack,
+                count,
+                height,
+            } => write!(
+                f,
+                "move #{}: cannot lift {} crates from stack {} holding {}",
+                index + 1,
+                count,
+                stack,
+                height
+            ),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Execution {
+    Checked,
+    Lenient,
+}
+
+#[derive(Debug, PartialEq, Clone)]
 struct Move {
     count: usize,
     from: usize,
     to: usize,
 }
 
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "move {} from {} to {}", self.count, self.from, self.to)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+struct Yard {
+    stacks: Vec<Vec<String>>,
+}
+
+impl Yard {
+    fn tops(&self) -> Vec<String> {
+        self.stacks
+            .iter()
+            .filter_map(|s| s.last().cloned())
+            .collect()
+    }
+
+    fn validate(&self, index: usize, step: &Move) -> Result<(), MoveError> {
+        for stack in [step.from, step.to] {
+            if stack == 0 || stack > self.stacks.len() {
+                return Err(MoveError::StackMissing { index, stack });
+            }
+        }
+        let height = self.stacks[step.from - 1].len();
+        if step.count > height {
+            return Err(MoveError::NotEnoughCrates {
+                index,
+                stack: step.from,
+                count: step.count,
+                height,
+            });
+        }
+        Ok(())
+    }
+
+    // Breadth first search over yard states, so the first plan found is one of the
+    // shortest, giving up once every plan of up to `max_depth` moves was tried or
+    // `max_states` yards were reached, as a large yard branches far too widely to
+    // search every plan
+    fn plan(
+        &self,
+        target: &str,
+        crane: &dyn CrateMover,
+        max_depth: usize,
+        max_states: usize,
+    ) -> Option<Vec<Move>> {
+        let mut seen = HashSet::from([self.clone()]);
+        let mut states: Vec<(Yard, Option<(usize, Move)>)> = vec![(self.clone(), None)];
+        let mut queue = VecDeque::from([(0, 0)]);
+        while let Some((state, depth)) = queue.pop_front() {
+            if states[state].0.tops().concat() == target {
+                let mut moves = vec![];
+                let mut current = state;
+                while let Some((parent, step)) = &states[current].1 {
+                    moves.push(step.clone());
+                    current = *parent;
+                }
+                moves.reverse();
+                return Some(moves);
+            }
+            if depth == max_depth {
+                continue;
+            }
+            let stacks = states[state].0.stacks.len();
+            for from in 1..=stacks {
+                for to in (1..=stacks).filter(|&to| to != from) {
+                    for count in 1..=states[state].0.stacks[from - 1].len() {
+                        let step = Move { count, from, to };
+                        let mut yard = states[state].0.clone();
+                        crane.execute(&mut yard, &step);
+                        if seen.insert(yard.clone()) {
+                            if states.len() >= max_states {
+                                return None;
+                            }
+                            queue.push_back((states.len(), depth + 1));
+                            states.push((yard, Some((state, step))));
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    // Checked execution refuses invalid moves, lenient execution lifts whatever
+    // crates are available instead, skipping moves to missing stacks, and hands
+    // back the error for the move it adjusted
+    fn apply(
+        &mut self,
+        crane: &dyn CrateMover,
+        index: usize,
+        step: &Move,
+        execution: Execution,
+    ) -> Result<Option<MoveError>, MoveError> {
+        match self.validate(index, step) {
+            Ok(()) => {
+                crane.execute(self, step);
+                Ok(None)
+            }
+            Err(err) if execution == Execution::Checked => Err(err),
+            Err(err @ MoveError::StackMissing { .. }) => Ok(Some(err)),
+            Err(err @ MoveError::NotEnoughCrates { height, .. }) => {
+                let step = Move {
+                    count: height,
+                    from: step.from,
+                    to: step.to,
+                };
+                crane.execute(self, &step);
+                Ok(Some(err))
+            }
+        }
+    }
+}
+
+trait CrateMover {
+    fn execute(&self, yard: &mut Yard, step: &Move);
+}
+
+// Lifts one crate at a time, reversing their order
+struct CrateMover9000;
+
+impl CrateMover for CrateMover9000 {
+    fn execute(&self, yard: &mut Yard, Move { count, from, to }: &Move) {
+        for _ in 0..*count {
+            if let Some(c) = yard.stacks[from - 1].pop() {
+                yard.stacks[to - 1].push(c);
+            }
+        }
+    }
+}
+
+// Lifts all crates at once, keeping their order
+struct CrateMover9001;
+
+impl CrateMover for CrateMover9001 {
+    fn execute(&self, yard: &mut Yard, Move { count, from, to }: &Move) {
+        let from_stack = &mut yard.stacks[from - 1];
+
+        let start = from_stack.len().saturating_sub(*count);
+        let mut to_move: Vec<_> = from_stack.drain(start..).collect();
+        let to_stack = &mut yard.stacks[to - 1];
+        to_stack.append(&mut to_move);
+    }
+}
+
+// Lifts at most `capacity` crates at once, splitting larger moves into chunks
+struct CapacityCrateMover {
+    capacity: NonZeroUsize,
+}
+
+impl CrateMover for CapacityCrateMover {
+    fn execute(&self, yard: &mut Yard, Move { count, from, to }: &Move) {
+        let mut remaining = *count;
+        while remaining > 0 {
+            let chunk = remaining.min(self.capacity.get());
+            let step = Move {
+                count: chunk,
+                from: *from,
+                to: *to,
+            };
+            CrateMover9001.execute(yard, &step);
+            remaining -= chunk;
+        }
+    }
+}
+
+// Pulls crates out from the bottom of the stack one at a time
+struct BottomCrateMover;
+
+impl CrateMover for BottomCrateMover {
+    fn execute(&self, yard: &mut Yard, Move { count, from, to }: &Move) {
+        let from_stack = &mut yard.stacks[from - 1];
+
+        let end = (*count).min(from_stack.len());
+        let mut to_move: Vec<_> = from_stack.drain(..end).collect();
+        let to_stack = &mut yard.stacks[to - 1];
+        to_stack.append(&mut to_move);
+    }
+}
+
+impl fmt::Display for Yard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let height = self.stacks.iter().map(Vec::len).max().unwrap_or(0);
+        let width = self
+            .stacks
+            .iter()
+            .flatten()
+            .map(|c| c.chars().count() + 2)
+            .chain([3])
+            .max()
+            .unwrap_or(3);
+        for level in (0..height).rev() {
+            let row: Vec<String> = self
+                .stacks
+                .iter()
+                .map(|stack| match stack.get(level) {
+                    Some(c) => format!("{:^width$}", format!("[{}]", c)),
+                    None => " ".repeat(width),
+                })
+                .collect();
+            writeln!(f, "{}", row.join(" "))?;
+        }
+        let labels: Vec<String> = (1..=self.stacks.len())
+            .map(|label| format!("{:^width$}", label))
+            .collect();
+        write!(f, "{}", labels.join(" "))
+    }
+}
+
 #[derive(Debug, PartialEq)]
 struct PuzzleInput {
-    yard: Vec<Vec<char>>,
+    yard: Yard,
     moves: Vec<Move>,
 }
 
 impl PuzzleInput {
-    fn tops(&self) -> Vec<char> {
-        self.yard.iter().filter_map(|s| s.last().cloned()).collect()
+    fn steps<'a>(
+        &'a self,
+        crane: &'a dyn CrateMover,
+        execution: Execution,
+    ) -> impl Iterator<Item = (&'a Move, Result<(Yard, Option<MoveError>), MoveError>)> + 'a {
+        self.moves
+            .iter()
+            .enumerate()
+            .scan(Some(self.yard.clone()), move |yard, (index, step)| {
+                let current = yard.as_mut()?;
+                match current.apply(crane, index, step, execution) {
+                    Ok(adjusted) => Some((step, Ok((current.clone(), adjusted)))),
+                    Err(err) => {
+                        *yard = None;
+                        Some((step, Err(err)))
+                    }
+                }
+            })
+    }
+}
+
+fn parse_input(input: &str) -> Result<PuzzleInput, Error> {
+    let lines: Vec<&str> = input.lines().collect();
+    let blank = lines
+        .iter()
+        .position(|line| line.trim().is_empty())
+        .unwrap_or(lines.len());
+    let (drawing, rest) = lines.split_at(blank);
+    let (label_row, rows) = drawing.split_last().ok_or(Error::MalformedRow {
+        line: 1,
+        column: 1,
+        reason: "missing the stack label row",
+    })?;
+    let labels = parse_labels(drawing.len(), label_row)?;
+
+    let mut stacks = vec![vec![]; labels.len()];
+    for (i, row) in rows.iter().enumerate().rev() {
+        for (stack, c) in parse_row(i + 1, row, &labels)? {
+            stacks[stack].push(c);
+        }
     }
+
+    let mut moves = vec![];
+    for line in rest
+        .iter()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+    {
+        match parse_move(line) {
+            Ok(("", step)) => moves.push(step),
+            _ => return Err(Error::Parsing),
+        }
+    }
+    Ok(PuzzleInput {
+        moves,
+        yard: Yard { stacks },
+    })
 }
 
-fn parse_input(input: &str) -> nom::IResult<&str, PuzzleInput> {
-    let (input, crate_slices) =
-        separated_list1(newline, separated_list1(tag(" "), parse_crate))(input)?;
-    let (input, labels) = preceded(multispace1, separated_list1(space1, digit1))(input)?;
+// Column span of each stack label, which crates in the rows above line up with
+fn parse_labels(line: usize, row: &str) -> Result<Vec<(usize, usize)>, Error> {
+    let mut labels = vec![];
+    let mut start = None;
+    for (column, c) in row.chars().chain([' ']).enumerate() {
+        match (c, start) {
+            (c, None) if c.is_ascii_digit() => start = Some(column),
+            (c, Some(_)) if c.is_ascii_digit() => {}
+            (c, Some(first)) if c.is_ascii_whitespace() => {
+                let label: usize = row[first..column].parse().or(Err(Error::Parsing))?;
+                if label != labels.len() + 1 {
+                    return Err(Error::MalformedRow {
+                        line,
+                        column: first + 1,
+                        reason: "stack labels must count up from 1",
+                    });
+                }
+                labels.push((first, column - 1));
+                start = None;
+            }
+            (c, None) if c.is_ascii_whitespace() => {}
+            // anything else stops here, so the row up to `column` is ASCII and
+            // slicing it by column is safe
+            _ => {
+                return Err(Error::MalformedRow {
+                    line,
+                    column: column + 1,
+                    reason: "stack labels must be numbers separated by spaces",
+                })
+            }
+        }
+    }
+    if labels.is_empty() {
+        return Err(Error::MalformedRow {
+            line,
+            column: 1,
+            reason: "missing the stack label row",
+        });
+    }
+    Ok(labels)
+}
 
-    let total_stacks = labels.len();
-    let mut yard = vec![vec![]; total_stacks];
-    for cs in crate_slices.into_iter().rev() {
-        for (i, c) in cs
-            .into_iter()
+// Each crate belongs to the stack whose label sits underneath it, so missing
+// trailing padding and crates wider than a single character are accepted
+fn parse_row(
+    line: usize,
+    row: &str,
+    labels: &[(usize, usize)],
+) -> Result<Vec<(usize, String)>, Error> {
+    let mut crates: Vec<(usize, String)> = vec![];
+    let mut rest = row;
+    let mut column = 0;
+    while let Some(next) = rest.chars().next() {
+        if next.is_whitespace() {
+            rest = &rest[next.len_utf8()..];
+            column += 1;
+            continue;
+        }
+        let malformed = |reason| Error::MalformedRow {
+            line,
+            column: column + 1,
+            reason,
+        };
+        let (remaining, c) =
+            parse_crate(rest).or(Err(malformed("expected a crate like '[A]' or spaces")))?;
+        let end = column + c.chars().count() + 1;
+        let mut under = labels
+            .iter()
             .enumerate()
-            .flat_map(|(i, o)| o.map(|c| (i, c)))
-        {
-            yard[i].push(c);
+            .filter(|(_, &(first, last))| first <= end && last >= column)
+            .map(|(stack, _)| stack);
+        let stack = match (under.next(), under.next()) {
+            (Some(stack), None) => stack,
+            (None, _) => return Err(malformed("crate is not above any stack label")),
+            (Some(_), Some(_)) => {
+                return Err(malformed("crate is above more than one stack label"))
+            }
+        };
+        if crates.iter().any(|(other, _)| *other == stack) {
+            return Err(malformed("more than one crate above the same stack label"));
         }
+        crates.push((stack, c));
+        rest = remaining;
+        column = end + 1;
     }
-    let (input, moves) = preceded(multispace1, separated_list1(newline, parse_move))(input)?;
-    Ok((input, PuzzleInput { moves, yard }))
+    Ok(crates)
 }
 
-fn parse_crate(input: &str) -> nom::IResult<&str, Option<char>> {
-    let (input, c) = alt((
-        map(tag("   "), |_| None),
-        map(delimited(char('['), anychar, char(']')), Some),
-    ))(input)?;
-    Ok((input, c))
+fn parse_crate(input: &str) -> nom::IResult<&str, String> {
+    map(delimited(char('['), is_not("[] "), char(']')), String::from)(input)
 }
 
 fn parse_move(input: &str) -> nom::IResult<&str, Move> {
@@ -91,35 +577,86 @@ fn parse_move(input: &str) -> nom::IResult<&str, Move> {
     Ok((input, Move { count, from, to }))
 }
 
-fn part1(input: &str) -> Result<String, Error> {
-    let (_, mut puzzle) = parse_input(input).or(Err(Error::ParsingError))?;
-    for Move { count, from, to } in &puzzle.moves {
-        for _ in 0..*count {
-            if let Some(c) = puzzle.yard[from - 1].pop() {
-                puzzle.yard[to - 1].push(c);
-            }
+fn print_steps(input: &str, crane: &dyn CrateMover, execution: Execution) -> Result<(), Error> {
+    let puzzle = parse_input(input)?;
+    println!("{}\n", puzzle.yard);
+    for (step, yard) in puzzle.steps(crane, execution) {
+        let (yard, adjusted) = yard.map_err(Error::InvalidMove)?;
+        if let Some(err) = adjusted {
+            print_adjustment(&err);
         }
+        println!("{}\n{}\n", step, yard);
     }
-    Ok(puzzle.tops().into_iter().collect())
+    Ok(())
 }
 
-fn part2(input: &str) -> Result<String, Error> {
-    let (_, mut puzzle) = parse_input(input).or(Err(Error::ParsingError))?;
-    for Move { count, from, to } in &puzzle.moves {
-        let from_stack = &mut puzzle.yard[from - 1];
+fn print_adjustment(err: &MoveError) {
+    match err {
+        MoveError::StackMissing { .. } => eprintln!("Skipped {}", err),
+        MoveError::NotEnoughCrates { .. } => eprintln!("Clamped {}", err),
+    }
+}
 
-        let start = from_stack.len().saturating_sub(*count);
-        let mut to_move: Vec<_> = from_stack.drain(start..).collect();
-        let to_stack = &mut puzzle.yard[to - 1];
-        to_stack.append(&mut to_move);
+fn print_plan(
+    input: &str,
+    target: &str,
+    crane: &dyn CrateMover,
+    depth: usize,
+    states: usize,
+) -> Result<(), Error> {
+    let puzzle = parse_input(input)?;
+    let moves = puzzle
+        .yard
+        .plan(target, crane, depth, states)
+        .ok_or_else(|| Error::NoPlan {
+            target: target.to_string(),
+            depth,
+            states,
+        })?;
+    println!("{}\n", puzzle.yard);
+    for step in moves {
+        println!("{}", step);
     }
-    Ok(puzzle.tops().into_iter().collect())
+    Ok(())
+}
+
+// The tops of the stacks, along with every move lenient execution adjusted
+fn run(
+    input: &str,
+    crane: &dyn CrateMover,
+    execution: Execution,
+) -> Result<(String, Vec<MoveError>), Error> {
+    let mut puzzle = parse_input(input)?;
+    let mut adjusted = vec![];
+    for (index, step) in puzzle.moves.iter().enumerate() {
+        let adjustment = puzzle
+            .yard
+            .apply(crane, index, step, execution)
+            .map_err(Error::InvalidMove)?;
+        adjusted.extend(adjustment);
+    }
+    Ok((puzzle.yard.tops().into_iter().collect(), adjusted))
+}
+
+fn part1(input: &str) -> Result<String, Error> {
+    run(input, &CrateMover9000, Execution::Checked).map(|(tops, _)| tops)
+}
+
+fn part2(input: &str) -> Result<String, Error> {
+    run(input, &CrateMover9001, Execution::Checked).map(|(tops, _)| tops)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn stacks(stacks: &[&[&str]]) -> Vec<Vec<String>> {
+        stacks
+            .iter()
+            .map(|stack| stack.iter().map(|c| c.to_string()).collect())
+            .collect()
+    }
+
     static EXAMPLE_INPUT: &str = "    [D]    
 [N] [C]    
 [Z] [M] [P]
@@ -159,42 +696,258 @@ move 1 from 1 to 2";
 
     #[test]
     fn test_parse_crate() {
-        assert_eq!(parse_crate("   "), Ok(("", None)));
-        assert_eq!(parse_crate("[D]"), Ok(("", Some('D'))));
+        assert!(parse_crate("   ").is_err());
+        assert!(parse_crate("[]").is_err());
+        assert_eq!(parse_crate("[D]"), Ok(("", "D".to_string())));
+        assert_eq!(parse_crate("[AB] [C]"), Ok((" [C]", "AB".to_string())));
     }
 
     #[test]
     fn test_parse_input() {
         assert_eq!(
             parse_input(EXAMPLE_INPUT),
-            Ok((
-                "",
-                PuzzleInput {
-                    yard: vec![vec!['Z', 'N'], vec!['M', 'C', 'D'], vec!['P']],
-                    moves: vec![
-                        Move {
-                            count: 1,
-                            from: 2,
-                            to: 1
-                        },
-                        Move {
-                            count: 3,
-                            from: 1,
-                            to: 3
-                        },
-                        Move {
-                            count: 2,
-                            from: 2,
-                            to: 1
-                        },
-                        Move {
-                            count: 1,
-                            from: 1,
-                            to: 2
-                        }
-                    ]
-                }
-            ))
+            Ok(PuzzleInput {
+                yard: Yard {
+                    stacks: stacks(&[&["Z", "N"], &["M", "C", "D"], &["P"]])
+                },
+                moves: vec![
+                    Move {
+                        count: 1,
+                        from: 2,
+                        to: 1
+                    },
+                    Move {
+                        count: 3,
+                        from: 1,
+                        to: 3
+                    },
+                    Move {
+                        count: 2,
+                        from: 2,
+                        to: 1
+                    },
+                    Move {
+                        count: 1,
+                        from: 1,
+                        to: 2
+                    }
+                ]
+            })
         )
     }
+
+    #[test]
+    fn test_display_yard() {
+        let puzzle = parse_input(EXAMPLE_INPUT).unwrap();
+        assert_eq!(
+            puzzle.yard.to_string(),
+            "    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 "
+        );
+    }
+
+    #[test]
+    fn test_steps() {
+        let puzzle = parse_input(EXAMPLE_INPUT).unwrap();
+        let steps: Vec<(String, String)> = puzzle
+            .steps(&CrateMover9000, Execution::Checked)
+            .map(|(step, yard)| {
+                let (yard, adjusted) = yard.unwrap();
+                assert_eq!(adjusted, None);
+                let tops = yard.tops().into_iter().collect();
+                (step.to_string(), tops)
+            })
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                ("move 1 from 2 to 1".to_string(), "DCP".to_string()),
+                ("move 3 from 1 to 3".to_string(), "CZ".to_string()),
+                ("move 2 from 2 to 1".to_string(), "MZ".to_string()),
+                ("move 1 from 1 to 2".to_string(), "CMZ".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_other_cranes() -> Result<(), Error> {
+        assert_eq!(
+            run(
+                EXAMPLE_INPUT,
+                crane("capacity:1")?.as_ref(),
+                Execution::Checked
+            )?
+            .0,
+            "CMZ"
+        );
+        assert_eq!(
+            run(
+                EXAMPLE_INPUT,
+                crane("capacity:3")?.as_ref(),
+                Execution::Checked
+            )?
+            .0,
+            "MCD"
+        );
+        assert_eq!(
+            run(
+                EXAMPLE_INPUT,
+                crane("capacity:2")?.as_ref(),
+                Execution::Checked
+            )?
+            .0,
+            "MCZ"
+        );
+        assert_eq!(
+            run(EXAMPLE_INPUT, &BottomCrateMover, Execution::Checked)?.0,
+            "DCM"
+        );
+        assert!(matches!(crane("capacity:0"), Err(Error::UnknownCrane(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_checked_execution() {
+        let input = "[A]    \n[B] [C]\n 1   2 \n\nmove 2 from 2 to 1\nmove 1 from 0 to 1";
+        assert!(matches!(
+            part2(input),
+            Err(Error::InvalidMove(MoveError::NotEnoughCrates {
+                index: 0,
+                stack: 2,
+                count: 2,
+                height: 1
+            }))
+        ));
+        let input = "[A]    \n[B] [C]\n 1   2 \n\nmove 1 from 2 to 1\nmove 1 from 0 to 1";
+        assert!(matches!(
+            part2(input),
+            Err(Error::InvalidMove(MoveError::StackMissing {
+                index: 1,
+                stack: 0
+            }))
+        ));
+        let input = "[A]    \n[B] [C]\n 1   2 \n\nmove 1 from 1 to 3";
+        assert!(matches!(
+            part1(input),
+            Err(Error::InvalidMove(MoveError::StackMissing {
+                index: 0,
+                stack: 3
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_lenient_execution() -> Result<(), Error> {
+        let input = "[A]    \n[B] [C]\n 1   2 \n\nmove 2 from 2 to 1\nmove 1 from 0 to 1\nmove 5 from 1 to 2";
+        let adjusted = vec![
+            MoveError::NotEnoughCrates {
+                index: 0,
+                stack: 2,
+                count: 2,
+                height: 1,
+            },
+            MoveError::StackMissing { index: 1, stack: 0 },
+            MoveError::NotEnoughCrates {
+                index: 2,
+                stack: 1,
+                count: 5,
+                height: 3,
+            },
+        ];
+        assert_eq!(
+            run(input, &CrateMover9000, Execution::Lenient)?,
+            ("B".to_string(), adjusted)
+        );
+        let (tops, adjusted) = run(input, &CrateMover9001, Execution::Lenient)?;
+        assert_eq!(tops, "C");
+        assert_eq!(adjusted.len(), 3);
+
+        let puzzle = parse_input(input)?;
+        let steps: Vec<_> = puzzle
+            .steps(&CrateMover9000, Execution::Lenient)
+            .map(|(_, yard)| yard.map(|(_, adjusted)| adjusted))
+            .collect();
+        assert_eq!(
+            steps[1],
+            Ok(Some(MoveError::StackMissing { index: 1, stack: 0 }))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_irregular_yards() -> Result<(), Error> {
+        let trimmed = "    [D]\n[N] [C]\n[Z] [M] [P]\n 1   2   3\n\nmove 1 from 2 to 1\n";
+        assert_eq!(
+            parse_input(trimmed)?.yard.stacks,
+            stacks(&[&["Z", "N"], &["M", "C", "D"], &["P"]])
+        );
+
+        let wide = "[AB]      [EF]\n[CD] [XY] [GH]\n 1    2    3\n\nmove 1 from 1 to 2";
+        let puzzle = parse_input(wide)?;
+        assert_eq!(
+            puzzle.yard.stacks,
+            stacks(&[&["CD", "AB"], &["XY"], &["GH", "EF"]])
+        );
+        assert_eq!(parse_input(&puzzle.yard.to_string())?.yard, puzzle.yard);
+
+        let many = "                                        [K]\n\
+            [A] [B] [C] [D] [E] [F] [G] [H] [I] [J] [L]\n\
+             1   2   3   4   5   6   7   8   9  10  11\n\n\
+            move 2 from 11 to 1";
+        assert_eq!(part2(many)?, "KBCDEFGHIJ");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_malformed_rows() {
+        let malformed = |input| match parse_input(input) {
+            Err(Error::MalformedRow { line, column, .. }) => Some((line, column)),
+            _ => None,
+        };
+        assert_eq!(malformed("[A] B\n 1   2"), Some((1, 5)));
+        assert_eq!(malformed("[A] [B\n 1   2"), Some((1, 5)));
+        assert_eq!(malformed("[A]     [C]\n 1   2"), Some((1, 9)));
+        assert_eq!(malformed("[A] [B]\n 1   3"), Some((2, 6)));
+        assert_eq!(malformed("[A] [B]\n 1   x"), Some((2, 6)));
+        assert_eq!(malformed("[ABCDEF]\n 1   2"), Some((1, 1)));
+        assert_eq!(
+            malformed(" [A]\n\u{a0}1 \n\nmove 1 from 1 to 1\n"),
+            Some((2, 1))
+        );
+        assert_eq!(malformed("[A] [B]\n 1\u{2003}2"), Some((2, 3)));
+    }
+
+    #[test]
+    fn test_plan() -> Result<(), Error> {
+        let puzzle = parse_input(EXAMPLE_INPUT)?;
+        for (crane, target) in [
+            (&CrateMover9000 as &dyn CrateMover, "CMZ"),
+            (&CrateMover9001, "MCD"),
+            (&CrateMover9001, "PZ"),
+        ] {
+            let moves = puzzle.yard.plan(target, crane, 4, 100_000).unwrap();
+            assert!(moves.len() <= 4);
+            let replay = format!(
+                "{}\n\n{}",
+                puzzle.yard,
+                moves
+                    .iter()
+                    .map(Move::to_string)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+            assert_eq!(run(&replay, crane, Execution::Checked)?.0, target);
+        }
+        assert_eq!(puzzle.yard.plan("NDP", &CrateMover9000, 0, 1), Some(vec![]));
+        assert_eq!(
+            puzzle.yard.plan("DCP", &CrateMover9000, 1, 100_000),
+            Some(vec![Move {
+                count: 1,
+                from: 2,
+                to: 1
+            }])
+        );
+        assert_eq!(puzzle.yard.plan("DCP", &CrateMover9000, 1, 1), None);
+        assert_eq!(puzzle.yard.plan("XYZ", &CrateMover9000, 3, 100_000), None);
+        Ok(())
+    }
 }
@@ -1,14 +1,69 @@
-use std::io;
+use std::{
+    env,
+    io::{self, BufReader, Bytes, Read},
+};
 
 fn main() -> io::Result<()> {
-    let input = io::read_to_string(io::stdin())?;
-    match part1(&input) {
-        Ok(result) => println!("Part 1: {}", result),
-        Err(err) => eprintln!("Part 1: Error = {}", err),
+    let length = env::args().find_map(|arg| {
+        arg.strip_prefix("--length=")
+            .map(|length| length.parse::<usize>())
+    });
+    if env::args().any(|arg| arg == "--all") {
+        let length = match length {
+            Some(Ok(length)) => length,
+            Some(Err(err)) => {
+                eprintln!("Length: Error = {}", err);
+                return Ok(());
+            }
+            None => 4,
+        };
+        match Markers::new(io::stdin().lock(), length) {
+            Ok(markers) => {
+                for offset in markers {
+                    println!("{}", offset?);
+                }
+            }
+            Err(err) => eprintln!("Markers: Error = {}", err),
+        }
+        return Ok(());
     }
-    match part2(&input) {
-        Ok(result) => println!("Part 2: {}", result),
-        Err(err) => eprintln!("Part 2: Error = {}", err),
+    if env::args().any(|arg| arg == "--decode") {
+        let mut data = vec![];
+        io::stdin().read_to_end(&mut data)?;
+        for packet in decode(&data) {
+            println!(
+                "Packet at {}: {} bytes, {} messages",
+                packet.offset,
+                packet.payload.len(),
+                packet.messages.len()
+            );
+            for message in packet.messages {
+                println!(
+                    "  Message at {}: {}",
+                    message.offset,
+                    String::from_utf8_lossy(message.payload)
+                );
+            }
+        }
+        return Ok(());
+    }
+    let input = io::read_to_string(io::stdin())?;
+    match length {
+        Some(Ok(length)) => match start_of_packet(&input, length) {
+            Ok(result) => println!("Length {}: {}", length, result),
+            Err(err) => eprintln!("Length {}: Error = {}", length, err),
+        },
+        Some(Err(err)) => eprintln!("Length: Error = {}", err),
+        None => {
+            match part1(&input) {
+                Ok(result) => println!("Part 1: {}", result),
+                Err(err) => eprintln!("Part 1: Error = {}", err),
+            }
+            match part2(&input) {
+                Ok(result) => println!("Part 2: {}", result),
+                Err(err) => eprintln!("Part 2: Error = {}", err),
+            }
+        }
     }
     Ok(())
 }
@@ -16,52 +71,186 @@ fn main() -> io::Result<()> {
 type Error = Box<dyn std::error::Error>;
 type Result<T> = std::result::Result<T, Error>;
 
-trait Buffer<T> {
-    fn occurs(&self, value: T) -> usize;
+// Sliding window over the last `length` bytes, with a count of each byte value
+// so that pushing a byte updates the number of duplicates in constant time
+struct MarkerWindow {
+    length: usize,
+    counts: [usize; 256],
+    history: Vec<u8>,
+    pushed: usize,
+    duplicates: usize,
+}
+
+impl MarkerWindow {
+    fn new(length: usize) -> Self {
+        MarkerWindow {
+            length,
+            counts: [0; 256],
+            history: vec![0; length],
+            pushed: 0,
+            duplicates: 0,
+        }
+    }
+
+    // Returns true when the window is full and holds no repeated bytes
+    fn push(&mut self, byte: u8) -> bool {
+        let slot = self.pushed % self.length;
+        if self.pushed >= self.length {
+            let old = self.history[slot] as usize;
+            self.counts[old] -= 1;
+            if self.counts[old] > 0 {
+                self.duplicates -= 1;
+            }
+        }
+        if self.counts[byte as usize] > 0 {
+            self.duplicates += 1;
+        }
+        self.counts[byte as usize] += 1;
+        self.history[slot] = byte;
+        self.pushed += 1;
+        self.pushed >= self.length && self.duplicates == 0
+    }
+}
+
+// Streams bytes from the reader, yielding the offset just past every window of
+// `length` distinct bytes, including windows that overlap an earlier marker
+struct Markers<R: Read> {
+    bytes: Bytes<BufReader<R>>,
+    window: MarkerWindow,
+    offset: usize,
+}
+
+impl<R: Read> Markers<R> {
+    fn new(reader: R, length: usize) -> Result<Self> {
+        if length == 0 {
+            return Err("marker length must be at least 1".into());
+        }
+        // there are only 256 distinct byte values to fill the window with
+        if length > 256 {
+            return Err("marker length must be at most 256".into());
+        }
+        Ok(Markers {
+            bytes: BufReader::new(reader).bytes(),
+            window: MarkerWindow::new(length),
+            offset: 0,
+        })
+    }
 }
 
-impl<const S: usize> Buffer<char> for [char; S] {
-    fn occurs(&self, value: char) -> usize {
-        self.iter().filter(|c| **c == value).count()
+impl<R: Read> Iterator for Markers<R> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let byte = match self.bytes.next()? {
+                Ok(byte) => byte,
+                Err(err) => return Some(Err(err)),
+            };
+            self.offset += 1;
+            if self.window.push(byte) {
+                return Some(Ok(self.offset));
+            }
+        }
     }
 }
 
-fn start_of_packet<const LENGTH: usize>(input: &str) -> Result<usize> {
-    if input.len() < LENGTH {
+fn start_of_packet(input: impl AsRef<[u8]>, length: usize) -> Result<usize> {
+    let input = input.as_ref();
+    if input.len() < length {
         return Err(format!(
             "messages with length less than {} cannot have a start of packet marker",
-            LENGTH
+            length
         )
         .into());
     }
-    let mut chars = input.chars();
-    let mut buffer = [' '; LENGTH];
-    let mut duplicate_count = 0;
-    for (i, c) in chars.by_ref().take(LENGTH).enumerate() {
-        buffer[i] = c;
-        duplicate_count += buffer[..i].iter().filter(|&&o| o == c).count();
-    }
-    let mut pos = LENGTH;
-    for c in chars {
-        if duplicate_count == 0 {
-            return Ok(pos);
+    let mut markers = Markers::new(input, length)?;
+    match markers.next() {
+        Some(offset) => Ok(offset?),
+        None => Err("No start of packet found".into()),
+    }
+}
+
+const PACKET_MARKER: usize = 4;
+const MESSAGE_MARKER: usize = 14;
+
+#[derive(Debug, PartialEq)]
+struct Message<'a> {
+    offset: usize,
+    payload: &'a [u8],
+}
+
+#[derive(Debug, PartialEq)]
+struct Packet<'a> {
+    offset: usize,
+    payload: &'a [u8],
+    messages: Vec<Message<'a>>,
+}
+
+// Offsets just past every marker in the data, each starting at or after the end
+// of the one before. Windows starting where `allowed` says no are passed over.
+fn marker_offsets(data: &[u8], length: usize, allowed: impl Fn(usize) -> bool) -> Vec<usize> {
+    let mut window = MarkerWindow::new(length);
+    let mut offsets = vec![];
+    let mut from = 0;
+    for (i, &byte) in data.iter().enumerate() {
+        let end = i + 1;
+        if window.push(byte) && end - length >= from && allowed(end - length) {
+            offsets.push(end);
+            from = end;
         }
-        let replace_index = pos % LENGTH;
-        let to_replace = buffer[replace_index];
-        duplicate_count -= buffer.occurs(to_replace) - 1;
-        buffer[replace_index] = c;
-        duplicate_count += buffer.occurs(c) - 1;
-        pos += 1;
-    }
-    Err("No start of packet found".into())
+    }
+    offsets
+}
+
+// Splits the stream into packets at start-of-packet markers, with messages
+// found over the whole stream so the first is the one `part2` reports. A message
+// runs to the next message marker, and the only packet marker allowed to begin
+// inside one is the opening of that next marker, giving each message a packet.
+fn decode(data: &[u8]) -> Vec<Packet<'_>> {
+    let messages = marker_offsets(data, MESSAGE_MARKER, |_| true);
+    let message_starts: Vec<usize> = messages.iter().map(|end| end - MESSAGE_MARKER).collect();
+    let packets = marker_offsets(data, PACKET_MARKER, |start| match message_starts.first() {
+        Some(&first) if start > first => message_starts.binary_search(&start).is_ok(),
+        _ => true,
+    });
+
+    let packet_ends: Vec<usize> = packets
+        .iter()
+        .skip(1)
+        .map(|offset| offset - PACKET_MARKER)
+        .chain([data.len()])
+        .collect();
+    let message_ends: Vec<usize> = message_starts
+        .iter()
+        .skip(1)
+        .copied()
+        .chain([data.len()])
+        .collect();
+    packets
+        .iter()
+        .zip(packet_ends)
+        .map(|(&offset, end)| Packet {
+            offset,
+            payload: &data[offset..end],
+            messages: messages
+                .iter()
+                .zip(&message_ends)
+                .filter(|(&message, _)| offset <= message && message <= end)
+                .map(|(&message, &message_end)| Message {
+                    offset: message,
+                    payload: &data[message..message_end.min(end)],
+                })
+                .collect(),
+        })
+        .collect()
 }
 
 fn part1(input: &str) -> Result<usize> {
-    start_of_packet::<4>(input)
+    start_of_packet(input, 4)
 }
 
 fn part2(input: &str) -> Result<usize> {
-    start_of_packet::<14>(input)
+    start_of_packet(input, 14)
 }
 
 #[cfg(test)]
@@ -97,4 +286,99 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn runtime_lengths() -> Result<()> {
+        assert_eq!(start_of_packet(EXAMPLE_INPUTS[0], 1)?, 1);
+        assert_eq!(start_of_packet(EXAMPLE_INPUTS[0], 2)?, 2);
+        assert_eq!(start_of_packet("aabcd", 4)?, 5);
+        assert!(start_of_packet("aabcc", 4).is_err());
+        assert!(start_of_packet("abc", 4).is_err());
+        assert!(start_of_packet("abc", 0).is_err());
+
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(start_of_packet(&bytes, 256)?, 256);
+        assert!(start_of_packet(&bytes, 257).is_err());
+        assert!(start_of_packet("abcd", usize::MAX).is_err());
+        assert!(Markers::new("abcd".as_bytes(), 100_000_000_000).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn all_markers() -> Result<()> {
+        let offsets = Markers::new("abcabbdefaa".as_bytes(), 3)?.collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(offsets, vec![3, 4, 5, 8, 9, 10]);
+
+        let bytes: &[u8] = &[0xff, 0xfe, 0xff, 0x00, 0xfe];
+        let offsets = Markers::new(bytes, 3)?.collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(offsets, vec![4, 5]);
+
+        assert_eq!(start_of_packet("ééa", 3)?, 5);
+        assert!(Markers::new(bytes, 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn decode_packets_and_messages() {
+        let data = b"aaaawxyzqqqqabcdefghijklmnnnnnstuvvvv";
+        assert_eq!(
+            decode(data),
+            vec![
+                Packet {
+                    offset: 7,
+                    payload: b"zqqq",
+                    messages: vec![]
+                },
+                Packet {
+                    offset: 15,
+                    payload: b"defghijklmnnnnnstuvvvv",
+                    messages: vec![Message {
+                        offset: 25,
+                        payload: b"nnnnnstuvvvv"
+                    }]
+                },
+            ]
+        );
+        assert_eq!(decode(b"aaaa"), vec![]);
+    }
+
+    #[test]
+    fn decode_finds_part2_message() -> Result<()> {
+        let first_message = |input: &str| {
+            decode(input.as_bytes())
+                .into_iter()
+                .flat_map(|packet| packet.messages)
+                .map(|message| message.offset)
+                .next()
+        };
+        for input in EXAMPLE_INPUTS.into_iter().chain(["xxxxabcdefghijklmnopq"]) {
+            assert_eq!(first_message(input), Some(part2(input)?));
+        }
+
+        // the message marker starts inside the packet's own marker
+        let packets = decode(b"xxxxabcdefghijklmnnoopp");
+        assert_eq!(packets[0].offset, 7);
+        assert_eq!(
+            packets[0].messages,
+            vec![Message {
+                offset: 17,
+                payload: b"nnoopp"
+            }]
+        );
+        assert_eq!(packets.len(), 1);
+
+        // later distinct runs carry on the message instead of opening packets
+        assert_eq!(
+            decode(EXAMPLE_INPUTS[0].as_bytes()),
+            vec![Packet {
+                offset: 7,
+                payload: b"gbljsphdztnvjfqwrcgsmlb",
+                messages: vec![Message {
+                    offset: 19,
+                    payload: b"jfqwrcgsmlb"
+                }]
+            }]
+        );
+        Ok(())
+    }
 }
@@ -1,32 +1,125 @@
-use std::{
-    cell::RefCell,
-    fmt::Debug,
-    io,
-    rc::{Rc, Weak},
-    str::FromStr,
-};
+use std::{collections::HashSet, env, fmt::Debug, io, str::FromStr};
 
 use nom::{
     branch::alt,
-    bytes::{complete::take_while, streaming::tag},
-    character::{
-        complete::{alpha1, digit1, newline},
-        streaming::space1,
-    },
-    combinator::{map, map_res, opt},
-    multi::separated_list1,
-    sequence::{preceded, separated_pair},
+    bytes::complete::tag,
+    character::complete::{char, digit1, line_ending, multispace0, not_line_ending},
+    combinator::{map, map_res, opt, verify},
+    error::ErrorKind,
+    multi::{separated_list0, separated_list1},
+    sequence::{delimited, preceded, separated_pair},
 };
 
 fn main() -> io::Result<()> {
     let input = io::read_to_string(io::stdin())?;
-    match part1(&input) {
-        Ok(result) => println!("Part 1: {}", result),
-        Err(err) => eprintln!("Part 1: Error= {}", err),
+    let (flags, args): (Vec<String>, Vec<String>) =
+        env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    // JSON trees are turned into their canonical transcript up front, so
+    // everything downstream only ever deals with transcripts
+    let loaded = parse_flags(&flags).and_then(|options| match options.json {
+        true => Ok((FileTree::from_json(&input)?.transcript(), options)),
+        false => Ok((input, options)),
+    });
+    let (input, options) = match loaded {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            eprintln!("Error= {}", err);
+            return Ok(());
+        }
+    };
+    if args.is_empty() {
+        match part1(&input) {
+            Ok(result) => println!("Part 1: {}", result),
+            Err(err) => eprintln!("Part 1: Error= {}", err),
+        }
+        match part2(&input, &options.disk) {
+            Ok(result) => println!("Part 1: {}", result),
+            Err(err) => eprintln!("Part 1: Error= {}", err),
+        }
+    } else if let Err(err) = report(&input, &args, &options) {
+        eprintln!("{}: Error= {}", args[0], err);
     }
-    match part2(&input) {
-        Ok(result) => println!("Part 1: {}", result),
-        Err(err) => eprintln!("Part 1: Error= {}", err),
+    Ok(())
+}
+
+fn report(input: &str, args: &[String], options: &Options) -> Result<()> {
+    let commands: Commands = input.parse()?;
+    let file_system = FileTree::try_from(commands)?;
+    let Options { filter, disk, .. } = options;
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["json"] => print!("{}", file_system.to_json()),
+        ["transcript"] => print!("{}", file_system.transcript()),
+        ["plan"] => {
+            let plan = file_system.plan_cleanup(disk)?;
+            let freed: usize = plan.iter().map(|id| file_system.get(*id).disk_size()).sum();
+            println!(
+                "Free {} of {} bytes by deleting:",
+                freed,
+                disk.must_free_up(file_system.total_size())
+            );
+            for id in plan {
+                println!(
+                    "{}\t{}",
+                    file_system.get(id).disk_size(),
+                    file_system.path(id)
+                );
+            }
+        }
+        ["find", ref query @ ..] => {
+            let query = Query::parse(query)?;
+            match query.aggregate {
+                Some(aggregate) => match query.aggregate(aggregate, &file_system)? {
+                    Some(value) => println!("{}", value),
+                    None => println!("no matches"),
+                },
+                None => {
+                    for id in query.results(&file_system)? {
+                        println!(
+                            "{}\t{}",
+                            file_system.get(id).disk_size(),
+                            file_system.path(id)
+                        );
+                    }
+                }
+            }
+        }
+        ["tree"] => print!("{}", file_system.tree(filter)),
+        ["du"] => {
+            for (id, size) in file_system.du(filter) {
+                println!("{}\t{}", size, file_system.path(id));
+            }
+        }
+        ["top", n] => {
+            let n = n.parse().map_err(|_| format!("{} is not a count", n))?;
+            println!("Largest files:");
+            for id in file_system.largest_files(n, filter) {
+                println!(
+                    "{}\t{}",
+                    file_system.get(id).disk_size(),
+                    file_system.path(id)
+                );
+            }
+            println!("Largest directories:");
+            for id in file_system.largest_directories(n, filter) {
+                println!(
+                    "{}\t{}",
+                    file_system.get(id).disk_size(),
+                    file_system.path(id)
+                );
+            }
+        }
+        _ => {
+            for path in args {
+                match file_system.lookup(path) {
+                    Some(id) => println!(
+                        "{}: {}",
+                        file_system.path(id),
+                        file_system.get(id).disk_size()
+                    ),
+                    None => eprintln!("{}: Error= not found", path),
+                }
+            }
+        }
     }
     Ok(())
 }
@@ -34,98 +127,812 @@ fn main() -> io::Result<()> {
 type Error = String;
 type Result<T> = std::result::Result<T, Error>;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct NodeId(usize);
+
+// Every directory and file lives in one arena and refers to the others by index.
+// Directory sizes are kept up to date as files are added.
 struct FileTree {
-    root: Rc<RefCell<Directory>>,
-    cwd: Weak<RefCell<Directory>>,
+    nodes: Vec<FileType>,
+    cwd: NodeId,
 }
 
 impl FileTree {
+    const ROOT: NodeId = NodeId(0);
+
     fn new() -> Self {
-        let root = Rc::new(RefCell::new(Directory::root("/")));
-        let cwd = Rc::downgrade(&root);
-        Self { root, cwd }
-    }
-    fn change_directory(&mut self, path: &str) {
-        match path {
-            "/" => self.cwd = Rc::downgrade(&self.root),
-            ".." => {
-                let Some(strong) = self.cwd.upgrade() else {
-                    return;
-                };
-                let borrowed = strong.borrow_mut();
-                let Some(parent) = borrowed.parent.clone() else {
-                    return;
-                };
-                self.cwd = parent
+        Self {
+            nodes: vec![FileType::Directory(Directory::root("/"))],
+            cwd: Self::ROOT,
+        }
+    }
+
+    fn change_directory(&mut self, path: &str) -> Result<()> {
+        match self.lookup(path) {
+            Some(id) if matches!(self.get(id), FileType::Directory(_)) => {
+                self.cwd = id;
+                Ok(())
             }
-            _ => {
-                let Some(strong) = self.cwd.upgrade() else {
-                    return;
-                };
-                for child in &strong.as_ref().borrow().contents {
-                    if let FileType::Directory(child) = child {
-                        if child.as_ref().borrow().name == path {
-                            self.cwd = Rc::downgrade(child)
-                        }
-                    }
-                }
+            Some(_) => Err(format!("cd: {}: Not a directory", path)),
+            None => Err(format!("cd: {}: No such directory", path)),
+        }
+    }
+
+    // Listing the same directory again finds entries that already exist, so
+    // directories are left alone and files only have their size updated
+    fn mkdir(&mut self, name: impl Into<String>) -> Result<()> {
+        let name = name.into();
+        match self.existing(&name)? {
+            Some(id) if matches!(self.get(id), FileType::Directory(_)) => Ok(()),
+            Some(_) => Err(format!("mkdir: {}: File exists", name)),
+            None => {
+                let new_directory = Directory::new(name, self.cwd);
+                self.insert(FileType::Directory(new_directory));
+                Ok(())
             }
         }
     }
 
-    fn mkdir(&mut self, name: impl Into<String>) {
-        let Some(cwd) = self.cwd.upgrade() else {
-            return;
+    fn touch(&mut self, name: impl Into<String>, size: usize) -> Result<()> {
+        let name = name.into();
+        let previous_size = match self.existing(&name)? {
+            Some(id) => match &mut self.nodes[id.0] {
+                FileType::File(file) => std::mem::replace(&mut file.size, size),
+                FileType::Directory(_) => return Err(format!("touch: {}: Is a directory", name)),
+            },
+            None => {
+                let new_file = File::new(name, self.cwd, size);
+                self.insert(FileType::File(new_file));
+                0
+            }
         };
-        let new_directory = Directory::new(name, &cwd);
-        cwd.borrow_mut()
-            .contents
-            .push(FileType::Directory(Rc::new(RefCell::new(new_directory))));
+        let mut ancestor = Some(self.cwd);
+        while let Some(id) = ancestor {
+            if let FileType::Directory(directory) = &mut self.nodes[id.0] {
+                directory.total_size = directory.total_size - previous_size + size;
+            }
+            ancestor = self.parent(id);
+        }
+        Ok(())
     }
 
-    fn touch(&mut self, name: impl Into<String>, size: usize) {
-        let Some(cwd) = self.cwd.upgrade() else {
-            return;
+    fn existing(&self, name: &str) -> Result<Option<NodeId>> {
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\r', '\n']) {
+            return Err(format!("{:?} is not a valid name", name));
+        }
+        Ok(self.child(self.cwd, name))
+    }
+
+    fn insert(&mut self, node: FileType) {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(node);
+        if let FileType::Directory(cwd) = &mut self.nodes[self.cwd.0] {
+            cwd.contents.push(id);
+        }
+    }
+
+    fn get(&self, id: NodeId) -> &FileType {
+        &self.nodes[id.0]
+    }
+
+    fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.get(id).parent()
+    }
+
+    fn children(&self, id: NodeId) -> &[NodeId] {
+        match self.get(id) {
+            FileType::Directory(directory) => &directory.contents,
+            FileType::File(_) => &[],
+        }
+    }
+
+    fn child(&self, id: NodeId, name: &str) -> Option<NodeId> {
+        self.children(id)
+            .iter()
+            .copied()
+            .find(|child| self.get(*child).name() == name)
+    }
+
+    // Paths starting with '/' are resolved from the root, others from the cwd
+    fn lookup(&self, path: &str) -> Option<NodeId> {
+        let start = match path.starts_with('/') {
+            true => Self::ROOT,
+            false => self.cwd,
         };
-        let new_file = File::new(name, size);
-        cwd.borrow_mut().contents.push(FileType::File(new_file));
+        path.split('/')
+            .filter(|segment| !segment.is_empty() && *segment != ".")
+            .try_fold(start, |id, segment| match segment {
+                ".." => Some(self.parent(id).unwrap_or(id)),
+                _ => self.child(id, segment),
+            })
+    }
+
+    fn path(&self, id: NodeId) -> String {
+        let mut names = vec![];
+        let mut current = Some(id);
+        while let Some(id) = current.filter(|id| *id != Self::ROOT) {
+            names.push(self.get(id).name());
+            current = self.parent(id);
+        }
+        names.reverse();
+        format!("/{}", names.join("/"))
     }
 
-    fn directories(&self) -> DirectoryIterator {
+    fn directories(&self) -> DirectoryIterator<'_> {
+        self.directories_from(Self::ROOT)
+    }
+
+    fn directories_from(&self, id: NodeId) -> DirectoryIterator<'_> {
         DirectoryIterator {
-            stack: vec![self.root.clone()],
+            tree: self,
+            stack: vec![id],
         }
     }
 
+    fn depth(&self, id: NodeId) -> usize {
+        std::iter::successors(self.parent(id), |id| self.parent(*id)).count()
+    }
+
     fn total_size(&self) -> usize {
-        FileType::Directory(self.root.clone()).disk_size()
+        self.get(Self::ROOT).disk_size()
+    }
+
+    fn files(&self) -> impl Iterator<Item = (NodeId, &File)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, node)| match node {
+                FileType::File(file) => Some((NodeId(i), file)),
+                FileType::Directory(_) => None,
+            })
+    }
+
+    fn sorted_children(&self, id: NodeId) -> Vec<NodeId> {
+        let mut children = self.children(id).to_vec();
+        children.sort_by(|a, b| self.get(*a).name().cmp(self.get(*b).name()));
+        children
+    }
+
+    // Drawn in the puzzle's own notation, keeping only the entries that match the
+    // filter along with the directories leading to them
+    fn tree(&self, filter: &Filter) -> String {
+        let mut output = String::new();
+        self.draw(Self::ROOT, 0, filter, &mut output);
+        output
+    }
+
+    fn draw(&self, id: NodeId, depth: usize, filter: &Filter, output: &mut String) -> bool {
+        let mut below = String::new();
+        let mut any_below = false;
+        for child in self.sorted_children(id) {
+            any_below |= self.draw(child, depth + 1, filter, &mut below);
+        }
+        let node = self.get(id);
+        if !any_below && !filter.matches(node.name(), node.disk_size()) {
+            return false;
+        }
+        let kind = match node {
+            FileType::Directory(_) => "dir",
+            FileType::File(_) => "file",
+        };
+        output.push_str(&format!(
+            "{}- {} ({}, size={})\n",
+            "  ".repeat(depth),
+            node.name(),
+            kind,
+            node.disk_size()
+        ));
+        output.push_str(&below);
+        true
+    }
+
+    fn du(&self, filter: &Filter) -> Vec<(NodeId, usize)> {
+        let mut totals: Vec<(NodeId, usize)> = self
+            .directories()
+            .filter(|(_, directory)| filter.matches(&directory.name, directory.total_size()))
+            .map(|(id, directory)| (id, directory.total_size()))
+            .collect();
+        totals.sort_by(|(a, a_size), (b, b_size)| {
+            b_size
+                .cmp(a_size)
+                .then_with(|| self.path(*a).cmp(&self.path(*b)))
+        });
+        totals
+    }
+
+    fn largest_files(&self, n: usize, filter: &Filter) -> Vec<NodeId> {
+        let mut files: Vec<(NodeId, &File)> = self
+            .files()
+            .filter(|(_, file)| filter.matches(&file.name, file.size))
+            .collect();
+        files.sort_by(|(a, a_file), (b, b_file)| {
+            b_file
+                .size
+                .cmp(&a_file.size)
+                .then_with(|| self.path(*a).cmp(&self.path(*b)))
+        });
+        files.into_iter().take(n).map(|(id, _)| id).collect()
+    }
+
+    fn largest_directories(&self, n: usize, filter: &Filter) -> Vec<NodeId> {
+        self.du(filter)
+            .into_iter()
+            .take(n)
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    // Deleting a set of non-nested entries frees exactly the bytes of the files
+    // inside them, so the cheapest plan is a subset sum over files. Files at least
+    // as large as the target are only ever worth deleting on their own, and no
+    // plan worth keeping frees more than the target plus the largest small file.
+    fn plan_cleanup(&self, disk: &Disk) -> Result<Vec<NodeId>> {
+        let target = disk.must_free_up(self.total_size());
+        if target == 0 {
+            return Ok(vec![]);
+        }
+        if target > self.total_size() {
+            return Err(format!(
+                "cannot free {} bytes, only {} bytes are in files",
+                target,
+                self.total_size()
+            ));
+        }
+        let (large, small): (Vec<_>, Vec<_>) = self
+            .files()
+            .map(|(id, file)| (id, file.size))
+            .filter(|(_, size)| *size > 0)
+            .partition(|(_, size)| *size >= target);
+        let single = large.into_iter().min_by_key(|(_, size)| *size);
+        let mut limit = target + small.iter().map(|(_, size)| *size).max().unwrap_or(0);
+        if let Some((_, size)) = single {
+            limit = limit.min(size);
+        }
+        if limit > MAX_PLAN_BYTES {
+            return Err(format!(
+                "cannot plan for {} bytes exactly, the search table stops at {} bytes",
+                target, MAX_PLAN_BYTES
+            ));
+        }
+
+        let reachable = reachable_sums(small.iter().map(|(_, size)| *size), limit);
+        let best = (target..limit).find(|sum| is_reachable(&reachable, *sum));
+        let chosen: HashSet<NodeId> =
+            match (best.and_then(|sum| files_summing_to(&small, sum)), single) {
+                (Some(files), _) => files.into_iter().collect(),
+                (None, Some((id, _))) => HashSet::from([id]),
+                (None, None) => return Err(format!("cannot free {} bytes", target)),
+            };
+        let mut plan = vec![];
+        self.collapse(Self::ROOT, &chosen, &mut plan);
+        Ok(plan)
+    }
+
+    // Replaces chosen files by their directory wherever the whole directory goes,
+    // returning whether that was the case for this node
+    fn collapse(&self, id: NodeId, chosen: &HashSet<NodeId>, plan: &mut Vec<NodeId>) -> bool {
+        if let FileType::File(_) = self.get(id) {
+            return chosen.contains(&id);
+        }
+        let mut below = vec![];
+        let mut whole = true;
+        for child in self.sorted_children(id) {
+            if self.get(child).disk_size() == 0 {
+                continue;
+            }
+            let mut inner = vec![];
+            if self.collapse(child, chosen, &mut inner) {
+                below.push(child);
+            } else {
+                whole = false;
+                below.extend(inner);
+            }
+        }
+        if whole && id != Self::ROOT {
+            return true;
+        }
+        plan.extend(below);
+        false
+    }
+}
+
+impl FileTree {
+    // Directory sizes are written out for reading along, but are recomputed from
+    // the files on import so hand-edited trees never disagree with themselves
+    fn to_json(&self) -> String {
+        let mut output = String::new();
+        self.write_json(Self::ROOT, 0, &mut output);
+        output.push('\n');
+        output
+    }
+
+    fn write_json(&self, id: NodeId, depth: usize, output: &mut String) {
+        let indent = "  ".repeat(depth);
+        let node = self.get(id);
+        output.push_str(&format!("{}{{\n", indent));
+        output.push_str(&format!(
+            "{}  \"name\": {},\n",
+            indent,
+            json_string(node.name())
+        ));
+        let kind = match node {
+            FileType::Directory(_) => "dir",
+            FileType::File(_) => "file",
+        };
+        output.push_str(&format!("{}  \"type\": \"{}\",\n", indent, kind));
+        output.push_str(&format!("{}  \"size\": {}", indent, node.disk_size()));
+        if let FileType::Directory(_) = node {
+            let children = self.sorted_children(id);
+            output.push_str(&format!(",\n{}  \"contents\": [", indent));
+            for (i, child) in children.iter().enumerate() {
+                output.push_str(if i == 0 { "\n" } else { ",\n" });
+                self.write_json(*child, depth + 2, output);
+            }
+            if !children.is_empty() {
+                output.push_str(&format!("\n{}  ", indent));
+            }
+            output.push(']');
+        }
+        output.push_str(&format!("\n{}}}", indent));
+    }
+
+    fn from_json(input: &str) -> Result<Self> {
+        let json = match parse_json(input) {
+            Ok(("", json)) => json,
+            Err(nom::Err::Failure(err)) if err.code == ErrorKind::TooLarge => {
+                return Err(format!("JSON nested more than {} deep", MAX_JSON_DEPTH))
+            }
+            _ => return Err("Could not parse JSON".into()),
+        };
+        if json.field("type").and_then(Json::as_str) != Some("dir") {
+            return Err("the root of the tree must be a \"dir\"".into());
+        }
+        let mut file_system = FileTree::new();
+        file_system.import(&json)?;
+        Ok(file_system)
+    }
+
+    fn import(&mut self, directory: &Json) -> Result<()> {
+        let entries = match directory.field("contents") {
+            Some(Json::Array(entries)) => entries.as_slice(),
+            Some(_) => return Err("\"contents\" must be a list".into()),
+            None => &[],
+        };
+        for entry in entries {
+            let name = entry
+                .field("name")
+                .and_then(Json::as_str)
+                .ok_or("every entry needs a \"name\"")?;
+            match entry.field("type").and_then(Json::as_str) {
+                Some("dir") => {
+                    self.mkdir(name)?;
+                    self.change_directory(name)?;
+                    self.import(entry)?;
+                    self.change_directory("..")?;
+                }
+                Some("file") => {
+                    let size = entry
+                        .field("size")
+                        .and_then(Json::as_number)
+                        .ok_or_else(|| format!("{}: a file needs a \"size\"", name))?;
+                    self.touch(name, size)?;
+                }
+                _ => return Err(format!("{}: \"type\" must be \"dir\" or \"file\"", name)),
+            }
+        }
+        Ok(())
+    }
+
+    // Every directory is listed once, entries sorted by name, visiting the
+    // subdirectories in the same order and climbing back out after each
+    fn transcript(&self) -> String {
+        let mut output = String::from("$ cd /\n");
+        self.write_transcript(Self::ROOT, &mut output);
+        output
+    }
+
+    fn write_transcript(&self, id: NodeId, output: &mut String) {
+        let children = self.sorted_children(id);
+        if children.is_empty() {
+            return;
+        }
+        output.push_str("$ ls\n");
+        for child in &children {
+            match self.get(*child) {
+                FileType::Directory(directory) => {
+                    output.push_str(&format!("dir {}\n", directory.name))
+                }
+                FileType::File(file) => output.push_str(&format!("{} {}\n", file.size, file.name)),
+            }
+        }
+        for child in children {
+            if let FileType::Directory(directory) = self.get(child) {
+                output.push_str(&format!("$ cd {}\n", directory.name));
+                self.write_transcript(child, output);
+                output.push_str("$ cd ..\n");
+            }
+        }
     }
 }
 
-struct DirectoryIterator {
-    stack: Vec<Rc<RefCell<Directory>>>,
+// Beyond this, filling the planner's bitsets of reachable sums takes too long
+const MAX_PLAN_BYTES: usize = 1 << 24;
+
+// Every sum below `limit` that some subset of the sizes adds up to, as a bitset.
+// Words are updated from the top down so each size is only counted once.
+fn reachable_sums(sizes: impl Iterator<Item = usize>, limit: usize) -> Vec<u64> {
+    let words = limit.div_ceil(64);
+    let mut reachable = vec![0u64; words];
+    reachable[0] = 1;
+    for size in sizes {
+        let (shift_words, shift_bits) = (size / 64, size % 64);
+        for word in (shift_words..words).rev() {
+            let source = word - shift_words;
+            let mut shifted = reachable[source] << shift_bits;
+            if shift_bits > 0 && source > 0 {
+                shifted |= reachable[source - 1] >> (64 - shift_bits);
+            }
+            reachable[word] |= shifted;
+        }
+    }
+    reachable
+}
+
+fn is_reachable(reachable: &[u64], sum: usize) -> bool {
+    reachable[sum / 64] & (1 << (sum % 64)) != 0
+}
+
+// Walks a reachable sum back to the files making it up without a table per sum:
+// split the files in halves, find how much each half contributes, and recurse
+fn files_summing_to(files: &[(NodeId, usize)], sum: usize) -> Option<Vec<NodeId>> {
+    match files {
+        [(id, size)] if *size == sum => Some(vec![*id]),
+        [] | [_] => (sum == 0).then(Vec::new),
+        _ => {
+            let (left, right) = files.split_at(files.len() / 2);
+            let split = {
+                let left_sums = reachable_sums(left.iter().map(|(_, size)| *size), sum + 1);
+                let right_sums = reachable_sums(right.iter().map(|(_, size)| *size), sum + 1);
+                (0..=sum).find(|part| {
+                    is_reachable(&left_sums, *part) && is_reachable(&right_sums, sum - part)
+                })?
+            };
+            let mut chosen = files_summing_to(left, split)?;
+            chosen.extend(files_summing_to(right, sum - split)?);
+            Some(chosen)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Disk {
+    capacity: usize,
+    needed: usize,
+}
+
+impl Default for Disk {
+    fn default() -> Self {
+        Self {
+            capacity: 70000000,
+            needed: 30000000,
+        }
+    }
+}
+
+impl Disk {
+    fn must_free_up(&self, used: usize) -> usize {
+        let free_space = self.capacity.saturating_sub(used);
+        self.needed.saturating_sub(free_space)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Filter {
+    pattern: Option<String>,
+    min_size: Option<usize>,
+    max_size: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+struct Options {
+    filter: Filter,
+    disk: Disk,
+    json: bool,
+}
+
+fn parse_flags(flags: &[String]) -> Result<Options> {
+    let mut options = Options::default();
+    let Options { filter, disk, json } = &mut options;
+    for flag in flags {
+        let (name, value) = flag.split_once('=').unwrap_or((flag, ""));
+        let size = || {
+            value
+                .parse()
+                .map_err(|_| format!("{} is not a size", value))
+        };
+        match name {
+            "--name" => filter.pattern = Some(value.to_string()),
+            "--min-size" => filter.min_size = Some(size()?),
+            "--max-size" => filter.max_size = Some(size()?),
+            "--disk" => disk.capacity = size()?,
+            "--needed" => disk.needed = size()?,
+            "--json" => *json = true,
+            _ => return Err(format!("unknown option {}", flag)),
+        }
+    }
+    Ok(options)
+}
+
+impl Filter {
+    fn matches(&self, name: &str, size: usize) -> bool {
+        self.pattern
+            .as_ref()
+            .is_none_or(|pattern| glob_match(pattern, name))
+            && self.min_size.is_none_or(|min| size >= min)
+            && self.max_size.is_none_or(|max| size <= max)
+    }
+}
+
+// Shell style wildcards, where '*' matches any run of characters and '?' any one
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+// A find(1) style query such as `find / -type d -size -100000 -sum`. Sizes and
+// depths take `-N` for at most N, `+N` for at least N and `N` for exactly N,
+// where a directory's size is its total
+#[derive(Debug)]
+struct Query {
+    start: String,
+    predicates: Vec<Predicate>,
+    sort: Option<(SortKey, bool)>,
+    aggregate: Option<Aggregate>,
+}
+
+#[derive(Debug)]
+enum Predicate {
+    Type(Kind),
+    Name(String),
+    Extension(String),
+    Size(Comparison),
+    Depth(Comparison),
+    Total(Comparison),
 }
 
-impl Iterator for DirectoryIterator {
-    type Item = Rc<RefCell<Directory>>;
+#[derive(Debug, PartialEq)]
+enum Kind {
+    Directory,
+    File,
+}
+
+#[derive(Debug)]
+enum Comparison {
+    AtMost(usize),
+    AtLeast(usize),
+    Exactly(usize),
+}
+
+#[derive(Debug)]
+enum SortKey {
+    Name,
+    Path,
+    Size,
+    Depth,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Aggregate {
+    Sum,
+    Count,
+    Min,
+    Max,
+}
+
+impl Query {
+    fn parse(tokens: &[&str]) -> Result<Self> {
+        let mut query = Query {
+            start: "/".into(),
+            predicates: vec![],
+            sort: None,
+            aggregate: None,
+        };
+        let mut tokens = tokens.iter().copied().peekable();
+        if let Some(start) = tokens.next_if(|token| !token.starts_with('-')) {
+            query.start = start.into();
+        }
+        while let Some(token) = tokens.next() {
+            let mut argument = || {
+                tokens
+                    .next()
+                    .ok_or_else(|| format!("find: {} needs an argument", token))
+            };
+            match token {
+                "-type" => query.predicates.push(Predicate::Type(match argument()? {
+                    "d" => Kind::Directory,
+                    "f" => Kind::File,
+                    kind => return Err(format!("find: unknown type {}", kind)),
+                })),
+                "-name" => query.predicates.push(Predicate::Name(argument()?.into())),
+                "-ext" => query
+                    .predicates
+                    .push(Predicate::Extension(argument()?.into())),
+                "-size" => query.predicates.push(Predicate::Size(argument()?.parse()?)),
+                "-depth" => query
+                    .predicates
+                    .push(Predicate::Depth(argument()?.parse()?)),
+                "-total" => query
+                    .predicates
+                    .push(Predicate::Total(argument()?.parse()?)),
+                "-sort" => {
+                    let key = argument()?;
+                    let (key, descending) = match key.strip_prefix('-') {
+                        Some(key) => (key, true),
+                        None => (key, false),
+                    };
+                    let key = match key {
+                        "name" => SortKey::Name,
+                        "path" => SortKey::Path,
+                        "size" => SortKey::Size,
+                        "depth" => SortKey::Depth,
+                        _ => return Err(format!("find: cannot sort by {}", key)),
+                    };
+                    query.sort = Some((key, descending));
+                }
+                "-sum" => query.aggregate = Some(Aggregate::Sum),
+                "-count" => query.aggregate = Some(Aggregate::Count),
+                "-min" => query.aggregate = Some(Aggregate::Min),
+                "-max" => query.aggregate = Some(Aggregate::Max),
+                _ => return Err(format!("find: unknown predicate {}", token)),
+            }
+        }
+        Ok(query)
+    }
+
+    // Every directory below the start as the DirectoryIterator finds it,
+    // followed by its files
+    fn matches<'a>(&'a self, tree: &'a FileTree) -> Result<impl Iterator<Item = NodeId> + 'a> {
+        // like the CLI lookups, a relative start is taken from the root
+        let start = match tree.lookup(&format!("/{}", self.start)) {
+            Some(id) if matches!(tree.get(id), FileType::Directory(_)) => id,
+            Some(_) => return Err(format!("find: {}: Not a directory", self.start)),
+            None => return Err(format!("find: {}: No such directory", self.start)),
+        };
+        let base = tree.depth(start);
+        Ok(tree
+            .directories_from(start)
+            .flat_map(move |(id, directory)| {
+                let files = directory
+                    .contents
+                    .iter()
+                    .copied()
+                    .filter(move |child| matches!(tree.get(*child), FileType::File(_)));
+                std::iter::once(id).chain(files)
+            })
+            .filter(move |id| self.accepts(tree.get(*id), tree.depth(*id) - base)))
+    }
+
+    fn accepts(&self, node: &FileType, depth: usize) -> bool {
+        self.predicates.iter().all(|predicate| match predicate {
+            Predicate::Type(kind) => match node {
+                FileType::Directory(_) => *kind == Kind::Directory,
+                FileType::File(_) => *kind == Kind::File,
+            },
+            Predicate::Name(pattern) => glob_match(pattern, node.name()),
+            Predicate::Extension(extension) => node
+                .name()
+                .rsplit_once('.')
+                .is_some_and(|(stem, ext)| !stem.is_empty() && ext == extension),
+            Predicate::Size(comparison) => comparison.matches(node.disk_size()),
+            Predicate::Depth(comparison) => comparison.matches(depth),
+            Predicate::Total(comparison) => match node {
+                FileType::Directory(directory) => comparison.matches(directory.total_size()),
+                FileType::File(_) => false,
+            },
+        })
+    }
+
+    fn results(&self, tree: &FileTree) -> Result<Vec<NodeId>> {
+        let mut results: Vec<NodeId> = self.matches(tree)?.collect();
+        if let Some((key, descending)) = &self.sort {
+            results.sort_by_cached_key(|id| match key {
+                SortKey::Name => (0, tree.get(*id).name().to_string()),
+                SortKey::Path => (0, tree.path(*id)),
+                SortKey::Size => (tree.get(*id).disk_size(), tree.path(*id)),
+                SortKey::Depth => (tree.depth(*id), tree.path(*id)),
+            });
+            if *descending {
+                results.reverse();
+            }
+        }
+        Ok(results)
+    }
+
+    fn aggregate(&self, aggregate: Aggregate, tree: &FileTree) -> Result<Option<usize>> {
+        let sizes = self.matches(tree)?.map(|id| tree.get(id).disk_size());
+        Ok(match aggregate {
+            Aggregate::Sum => Some(sizes.sum()),
+            Aggregate::Count => Some(sizes.count()),
+            Aggregate::Min => sizes.min(),
+            Aggregate::Max => sizes.max(),
+        })
+    }
+}
+
+impl FromStr for Comparison {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let number = |n: &str| {
+            n.parse()
+                .map_err(|_| format!("find: {} is not a number", s))
+        };
+        match (s.strip_prefix('-'), s.strip_prefix('+')) {
+            (Some(n), _) => Ok(Comparison::AtMost(number(n)?)),
+            (_, Some(n)) => Ok(Comparison::AtLeast(number(n)?)),
+            _ => Ok(Comparison::Exactly(number(s)?)),
+        }
+    }
+}
+
+impl Comparison {
+    fn matches(&self, value: usize) -> bool {
+        match *self {
+            Comparison::AtMost(n) => value <= n,
+            Comparison::AtLeast(n) => value >= n,
+            Comparison::Exactly(n) => value == n,
+        }
+    }
+}
+
+struct DirectoryIterator<'a> {
+    tree: &'a FileTree,
+    stack: Vec<NodeId>,
+}
+
+impl<'a> Iterator for DirectoryIterator<'a> {
+    type Item = (NodeId, &'a Directory);
     fn next(&mut self) -> Option<Self::Item> {
-        let Some(next) = self.stack.pop() else {
+        let next = self.stack.pop()?;
+        let FileType::Directory(directory) = self.tree.get(next) else {
             return None;
         };
-
-        for child in &next.as_ref().borrow().contents {
-            if let FileType::Directory(child) = child {
-                self.stack.push(child.clone())
+        for child in &directory.contents {
+            if let FileType::Directory(_) = self.tree.get(*child) {
+                self.stack.push(*child)
             }
         }
-        Some(next)
+        Some((next, directory))
     }
 }
 
 struct Directory {
     name: String,
-    parent: Option<Weak<RefCell<Directory>>>,
-    contents: Vec<FileType>,
+    parent: Option<NodeId>,
+    contents: Vec<NodeId>,
+    total_size: usize,
 }
 
 impl Directory {
@@ -134,32 +941,35 @@ impl Directory {
             name: name.into(),
             parent: None,
             contents: vec![],
+            total_size: 0,
         }
     }
 
-    fn new(name: impl Into<String>, parent: &Rc<RefCell<Directory>>) -> Self {
-        let parent = Rc::downgrade(parent);
+    fn new(name: impl Into<String>, parent: NodeId) -> Self {
         Self {
             name: name.into(),
             parent: Some(parent),
             contents: vec![],
+            total_size: 0,
         }
     }
 
     fn total_size(&self) -> usize {
-        self.contents.iter().map(|c| c.disk_size()).sum()
+        self.total_size
     }
 }
 
 struct File {
-    _name: String,
+    name: String,
+    parent: NodeId,
     size: usize,
 }
 
 impl File {
-    fn new(name: impl Into<String>, size: usize) -> Self {
+    fn new(name: impl Into<String>, parent: NodeId, size: usize) -> Self {
         Self {
-            _name: name.into(),
+            name: name.into(),
+            parent,
             size,
         }
     }
@@ -167,13 +977,27 @@ impl File {
 
 enum FileType {
     File(File),
-    Directory(Rc<RefCell<Directory>>),
+    Directory(Directory),
 }
 
 impl FileType {
+    fn name(&self) -> &str {
+        match self {
+            Self::Directory(directory) => &directory.name,
+            Self::File(file) => &file.name,
+        }
+    }
+
+    fn parent(&self) -> Option<NodeId> {
+        match self {
+            Self::Directory(directory) => directory.parent,
+            Self::File(file) => Some(file.parent),
+        }
+    }
+
     fn disk_size(&self) -> usize {
         match self {
-            Self::Directory(directory) => directory.as_ref().borrow().total_size(),
+            Self::Directory(directory) => directory.total_size(),
             Self::File(file) => file.size,
         }
     }
@@ -184,61 +1008,75 @@ struct Commands(Vec<Command>);
 impl FromStr for Commands {
     type Err = String;
     fn from_str(input: &str) -> std::result::Result<Self, Self::Err> {
-        let Ok((_, commands)) = separated_list1(newline, parse_command)(input) else {
-            return Err("Could not parse input".into())
+        let Ok((rest, commands)) = separated_list1(line_ending, parse_command)(input) else {
+            return Err("Could not parse input".into());
         };
+        let unparsed = rest
+            .split_inclusive('\n')
+            .enumerate()
+            .find(|(_, line)| !line.trim().is_empty());
+        if let Some((skipped, line)) = unparsed {
+            let consumed = &input[..input.len() - rest.len()];
+            let line_number = consumed.matches('\n').count() + skipped + 1;
+            let line = line.trim_end_matches(['\r', '\n']);
+            return Err(format!(
+                "Could not parse input at line {}: {}",
+                line_number, line
+            ));
+        }
         Ok(Commands(commands))
     }
 }
 
-impl From<Commands> for FileTree {
-    fn from(commands: Commands) -> Self {
+impl TryFrom<Commands> for FileTree {
+    type Error = Error;
+    fn try_from(commands: Commands) -> Result<Self> {
         let mut file_system = FileTree::new();
 
         for command in commands.0 {
             match command {
-                Command::ChangeDirectory(directory) => file_system.change_directory(&directory),
+                Command::ChangeDirectory(directory) => file_system.change_directory(&directory)?,
                 Command::List(entries) => {
                     for entry in entries {
                         match entry {
                             ListOutputEntry::Directory(directory) => {
-                                file_system.mkdir(&directory);
+                                file_system.mkdir(&directory)?
+                            }
+                            ListOutputEntry::File { name, size } => {
+                                file_system.touch(name, size)?
                             }
-                            ListOutputEntry::File { name, size } => file_system.touch(name, size),
                         }
                     }
                 }
             }
         }
-        file_system
+        // the transcript's last cd says nothing about later lookups
+        file_system.cwd = FileTree::ROOT;
+        Ok(file_system)
     }
 }
 
 fn part1(input: &str) -> Result<usize> {
     let commands: Commands = input.parse()?;
-    let file_system = FileTree::from(commands);
+    let file_system = FileTree::try_from(commands)?;
 
     let total_size = file_system
         .directories()
-        .map(|directory| directory.as_ref().borrow().total_size())
+        .map(|(_, directory)| directory.total_size())
         .filter(|s| *s <= 100000)
         .sum();
     Ok(total_size)
 }
 
-fn part2(input: &str) -> Result<usize> {
+fn part2(input: &str, disk: &Disk) -> Result<usize> {
     let commands: Commands = input.parse()?;
-    let file_system = FileTree::from(commands);
+    let file_system = FileTree::try_from(commands)?;
 
-    let total_used = file_system.total_size();
-    let total_disk_space: usize = 70000000;
-    let free_space = total_disk_space.saturating_sub(total_used);
-    let needed_for_patch: usize = 30000000;
-    let must_free_up = needed_for_patch.saturating_sub(free_space);
+    let must_free_up = disk.must_free_up(file_system.total_size());
 
     let space_to_free_up = file_system
         .directories()
-        .map(|directory| directory.as_ref().borrow().total_size())
+        .map(|(_, directory)| directory.total_size())
         .filter(|s| *s >= must_free_up)
         .min()
         .unwrap_or_default();
@@ -257,16 +1095,19 @@ enum ListOutputEntry {
     File { name: String, size: usize },
 }
 
+fn parse_rest_of_line(input: &str) -> nom::IResult<&str, &str> {
+    verify(not_line_ending, |rest: &str| !rest.trim().is_empty())(input)
+}
+
 fn parse_change_directory_command(input: &str) -> nom::IResult<&str, Command> {
-    map(
-        preceded(tag("cd "), alt((alpha1, tag("/"), tag("..")))),
-        |d: &str| Command::ChangeDirectory(d.into()),
-    )(input)
+    map(preceded(tag("cd "), parse_rest_of_line), |d: &str| {
+        Command::ChangeDirectory(d.into())
+    })(input)
 }
 
 fn parse_ls_command(input: &str) -> nom::IResult<&str, Command> {
     let (input, _) = tag("ls")(input)?;
-    let (input, entries) = opt(preceded(newline, parse_ls_output))(input)?;
+    let (input, entries) = opt(preceded(line_ending, parse_ls_output))(input)?;
     Ok((input, Command::List(entries.unwrap_or_default())))
 }
 
@@ -278,7 +1119,7 @@ fn parse_command(input: &str) -> nom::IResult<&str, Command> {
 }
 
 fn parse_ls_directory_entry(input: &str) -> nom::IResult<&str, ListOutputEntry> {
-    map(preceded(tag("dir "), alpha1), |name: &str| {
+    map(preceded(tag("dir "), parse_rest_of_line), |name: &str| {
         ListOutputEntry::Directory(name.to_string())
     })(input)
 }
@@ -287,8 +1128,8 @@ fn parse_ls_file_entry(input: &str) -> nom::IResult<&str, ListOutputEntry> {
     map(
         separated_pair(
             map_res(digit1, |size: &str| size.parse()),
-            space1,
-            take_while(|c: char| c.is_alphanumeric() || c == '.'),
+            char(' '),
+            parse_rest_of_line,
         ),
         |(size, name)| ListOutputEntry::File {
             name: name.to_string(),
@@ -299,11 +1140,143 @@ fn parse_ls_file_entry(input: &str) -> nom::IResult<&str, ListOutputEntry> {
 
 fn parse_ls_output(input: &str) -> nom::IResult<&str, Vec<ListOutputEntry>> {
     separated_list1(
-        newline,
+        line_ending,
         alt((parse_ls_directory_entry, parse_ls_file_entry)),
     )(input)
 }
 
+// Just enough JSON for the exported trees: objects, lists, strings and sizes
+#[derive(Debug, PartialEq)]
+enum Json {
+    Object(Vec<(String, Json)>),
+    Array(Vec<Json>),
+    String(String),
+    Number(usize),
+}
+
+impl Json {
+    fn field(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_number(&self) -> Option<usize> {
+        match self {
+            Json::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut output = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\t' => output.push_str("\\t"),
+            c if c.is_control() => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+// Deep enough for any directory tree worth keeping, shallow enough that neither
+// the parser nor `import` can run out of stack
+const MAX_JSON_DEPTH: usize = 256;
+
+fn parse_json(input: &str) -> nom::IResult<&str, Json> {
+    parse_json_nested(input, 0)
+}
+
+fn parse_json_nested(input: &str, depth: usize) -> nom::IResult<&str, Json> {
+    if depth >= MAX_JSON_DEPTH && input.trim_start().starts_with(['[', '{']) {
+        return Err(nom::Err::Failure(nom::error::Error::new(
+            input,
+            ErrorKind::TooLarge,
+        )));
+    }
+    let value = |input| parse_json_nested(input, depth + 1);
+    delimited(
+        multispace0,
+        alt((
+            map(parse_json_string, Json::String),
+            map(map_res(digit1, str::parse), Json::Number),
+            map(
+                delimited(
+                    char('['),
+                    separated_list0(char(','), value),
+                    preceded(multispace0, char(']')),
+                ),
+                Json::Array,
+            ),
+            map(
+                delimited(
+                    char('{'),
+                    separated_list0(
+                        char(','),
+                        separated_pair(
+                            delimited(multispace0, parse_json_string, multispace0),
+                            char(':'),
+                            value,
+                        ),
+                    ),
+                    preceded(multispace0, char('}')),
+                ),
+                Json::Object,
+            ),
+        )),
+        multispace0,
+    )(input)
+}
+
+fn parse_json_string(input: &str) -> nom::IResult<&str, String> {
+    let error = |input| nom::Err::Error(nom::error::Error::new(input, ErrorKind::Char));
+    let (mut rest, _) = char('"')(input)?;
+    let mut output = String::new();
+    loop {
+        let mut chars = rest.chars();
+        match chars.next() {
+            Some('"') => return Ok((chars.as_str(), output)),
+            Some('\\') => {
+                let escaped = match chars.next() {
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some('/') => '/',
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('b') => '\u{8}',
+                    Some('f') => '\u{c}',
+                    Some('u') => {
+                        let hex = chars.as_str().get(..4).ok_or_else(|| error(rest))?;
+                        let code = u32::from_str_radix(hex, 16).map_err(|_| error(rest))?;
+                        chars = chars.as_str()[4..].chars();
+                        char::from_u32(code).ok_or_else(|| error(rest))?
+                    }
+                    _ => return Err(error(rest)),
+                };
+                output.push(escaped);
+            }
+            Some(c) => output.push(c),
+            None => return Err(error(rest)),
+        }
+        rest = chars.as_str();
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -338,7 +1311,7 @@ $ ls
 
     #[test]
     fn test_part2() {
-        assert_eq!(part2(EXAMPLE_INPUT), Ok(24933642));
+        assert_eq!(part2(EXAMPLE_INPUT, &Disk::default()), Ok(24933642));
     }
 
     #[test]
@@ -383,6 +1356,14 @@ dir d";
             parse_command("$ cd .."),
             Ok(("", Command::ChangeDirectory("..".into())))
         );
+        assert_eq!(
+            parse_command("$ cd my-dir.v2"),
+            Ok(("", Command::ChangeDirectory("my-dir.v2".into())))
+        );
+        assert_eq!(
+            parse_command("$ cd /a/b\n$ ls"),
+            Ok(("\n$ ls", Command::ChangeDirectory("/a/b".into())))
+        );
     }
 
     #[test]
@@ -410,4 +1391,318 @@ dir d";
             ))
         )
     }
+
+    #[test]
+    fn test_file_tree_navigation() -> Result<()> {
+        let file_system = FileTree::try_from(EXAMPLE_INPUT.parse::<Commands>()?)?;
+        let e = file_system.lookup("/a/e").ok_or("missing /a/e")?;
+        assert_eq!(file_system.get(e).disk_size(), 584);
+        assert_eq!(file_system.path(e), "/a/e");
+
+        let a = file_system.parent(e).ok_or("missing parent")?;
+        assert_eq!(file_system.path(a), "/a");
+        assert_eq!(file_system.get(a).disk_size(), 94853);
+        let names: Vec<&str> = file_system
+            .children(a)
+            .iter()
+            .map(|child| file_system.get(*child).name())
+            .collect();
+        assert_eq!(names, vec!["e", "f", "g", "h.lst"]);
+
+        let log = file_system.lookup("/d/d.log").ok_or("missing /d/d.log")?;
+        assert_eq!(file_system.path(log), "/d/d.log");
+        assert_eq!(file_system.get(log).disk_size(), 8033020);
+        assert_eq!(file_system.lookup("/"), Some(FileTree::ROOT));
+        assert_eq!(file_system.path(FileTree::ROOT), "/");
+        assert_eq!(file_system.lookup("/a/missing"), None);
+        // the transcript ends in /d, but relative paths start from the root
+        assert_eq!(file_system.lookup("a/e"), Some(e));
+        assert_eq!(file_system.lookup("d.log"), None);
+        assert_eq!(file_system.total_size(), 48381165);
+        Ok(())
+    }
+
+    #[test]
+    fn test_full_transcripts() -> Result<()> {
+        let transcript = "$ cd /\r
+$ ls\r
+dir my-dir.v2\r
+dir a\r
+10 top level.txt\r
+$ cd /a\r
+$ ls\r
+dir b\r
+$ cd b\r
+$ ls\r
+20 x_y-z.tar.gz\r
+$ cd ../../my-dir.v2\r
+$ ls\r
+5 c\r
+$ ls\r
+7 c\r
+$ cd /a/b\r
+$ ls\r
+20 x_y-z.tar.gz\r
+";
+        let file_system = FileTree::try_from(transcript.parse::<Commands>()?)?;
+        assert_eq!(file_system.total_size(), 37);
+        let b = file_system.lookup("/a/b").ok_or("missing /a/b")?;
+        assert_eq!(file_system.get(b).disk_size(), 20);
+        assert_eq!(file_system.children(b).len(), 1);
+        let c = file_system.lookup("/my-dir.v2/c").ok_or("missing c")?;
+        assert_eq!(file_system.get(c).disk_size(), 7);
+        assert!(file_system.lookup("/top level.txt").is_some());
+
+        let unknown = "$ cd /\n$ ls\ndir a\n$ cd b";
+        assert_eq!(
+            FileTree::try_from(unknown.parse::<Commands>()?).err(),
+            Some("cd: b: No such directory".to_string())
+        );
+        let into_file = "$ cd /\n$ ls\n1 a\n$ cd a";
+        assert_eq!(
+            FileTree::try_from(into_file.parse::<Commands>()?).err(),
+            Some("cd: a: Not a directory".to_string())
+        );
+        assert_eq!(
+            "$ cd /\n$ ls\n1 a\n$ rm a".parse::<Commands>().err(),
+            Some("Could not parse input at line 4: $ rm a".to_string())
+        );
+        assert_eq!(
+            "$ cd /\n$ ls\n\n$ cd a".parse::<Commands>().err(),
+            Some("Could not parse input at line 4: $ cd a".to_string())
+        );
+        assert_eq!(
+            "$ cd /\r\n$ ls\r\n\r\n\r\n$ cd a\r\n"
+                .parse::<Commands>()
+                .err(),
+            Some("Could not parse input at line 5: $ cd a".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_cleanup() -> Result<()> {
+        let file_system = FileTree::try_from(EXAMPLE_INPUT.parse::<Commands>()?)?;
+        let plan = |needed| -> Result<Vec<String>> {
+            let disk = Disk {
+                needed,
+                ..Disk::default()
+            };
+            let plan = file_system.plan_cleanup(&disk)?;
+            Ok(plan.into_iter().map(|id| file_system.path(id)).collect())
+        };
+        assert_eq!(plan(30000000)?, vec!["/c.dat"]);
+        assert_eq!(plan(20000000)?, Vec::<String>::new());
+        // exactly the contents of /a, which is deleted as a whole
+        assert_eq!(plan(21618835 + 94853)?, vec!["/a"]);
+        assert_eq!(plan(21618835 + 62600)?, vec!["/a/e", "/a/h.lst"]);
+        assert!(plan(100000000).is_err());
+        assert_eq!(
+            plan(21618835 + 20000000).err(),
+            Some(format!(
+                "cannot plan for 20000000 bytes exactly, the search table stops at {} bytes",
+                MAX_PLAN_BYTES
+            ))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_round_trip() -> Result<()> {
+        let file_system = FileTree::try_from(EXAMPLE_INPUT.parse::<Commands>()?)?;
+        let transcript = file_system.transcript();
+        assert!(transcript.starts_with("$ cd /\n$ ls\ndir a\n14848514 b.txt\n"));
+        assert!(transcript.ends_with(
+            "$ cd d\n$ ls\n5626152 d.ext\n8033020 d.log\n4060174 j\n7214296 k\n$ cd ..\n"
+        ));
+        let replayed = FileTree::try_from(transcript.parse::<Commands>()?)?;
+        assert_eq!(replayed.transcript(), transcript);
+
+        let json = file_system.to_json();
+        let imported = FileTree::from_json(&json)?;
+        assert_eq!(imported.to_json(), json);
+        assert_eq!(imported.transcript(), transcript);
+
+        let edited = r#"{"type": "dir", "size": 1, "contents": [
+            {"name": "my \"notes\"", "type": "dir", "contents": [
+                {"name": "caf\u00e9.txt", "type": "file", "size": 12}
+            ]},
+            {"name": "empty", "type": "dir", "contents": []}
+        ]}"#;
+        let imported = FileTree::from_json(edited)?;
+        assert_eq!(imported.total_size(), 12);
+        let file = imported.lookup("/my \"notes\"/café.txt").unwrap();
+        assert_eq!(imported.get(file).disk_size(), 12);
+        assert_eq!(
+            imported.transcript(),
+            "$ cd /\n$ ls\ndir empty\ndir my \"notes\"\n$ cd empty\n$ cd ..\n$ cd my \"notes\"\n$ ls\n12 café.txt\n$ cd ..\n"
+        );
+
+        assert!(FileTree::from_json(r#"{"type": "file", "size": 1}"#).is_err());
+        let nested = |depth| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(parse_json(&nested(MAX_JSON_DEPTH)).is_ok());
+        assert!(parse_json(&nested(MAX_JSON_DEPTH + 1)).is_err());
+        assert_eq!(
+            FileTree::from_json(&nested(200000)).err(),
+            Some(format!("JSON nested more than {} deep", MAX_JSON_DEPTH))
+        );
+        let deep_tree = (0..100).fold(String::from("[]"), |contents, i| {
+            format!(
+                r#"[{{"name": "d{}", "type": "dir", "contents": {}}}]"#,
+                i, contents
+            )
+        });
+        let deep_tree = format!(r#"{{"type": "dir", "contents": {}}}"#, deep_tree);
+        assert!(FileTree::from_json(&deep_tree).is_ok());
+        assert!(FileTree::from_json(
+            r#"{"type": "dir", "contents": [{"name": "x", "type": "file"}]}"#
+        )
+        .is_err());
+        assert!(FileTree::from_json(
+            r#"{"type": "dir", "contents": [{"name": "..", "type": "dir"}]}"#
+        )
+        .is_err());
+        assert!(FileTree::from_json(r#"{"type": "dir"} trailing"#).is_err());
+
+        // names that a transcript could not carry are refused up front
+        let newline =
+            r#"{"type": "dir", "contents": [{"name": "a\nb", "type": "file", "size": 1}]}"#;
+        assert!(FileTree::from_json(newline).is_err());
+        let carriage_return = r#"{"type": "dir", "contents": [{"name": "a\rb", "type": "dir"}]}"#;
+        assert!(FileTree::from_json(carriage_return).is_err());
+        let spaced = r#"{"type": "dir", "contents": [
+            {"name": " x", "type": "file", "size": 1},
+            {"name": "  y ", "type": "dir", "contents": [{"name": " z", "type": "file", "size": 2}]}
+        ]}"#;
+        let imported = FileTree::from_json(spaced)?;
+        let replayed = FileTree::try_from(imported.transcript().parse::<Commands>()?)?;
+        assert_eq!(replayed.to_json(), imported.to_json());
+        assert!(replayed.lookup("/ x").is_some());
+        assert!(replayed.lookup("/  y / z").is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_find() -> Result<()> {
+        let file_system = FileTree::try_from(EXAMPLE_INPUT.parse::<Commands>()?)?;
+        let query = |q: &str| Query::parse(&q.split_whitespace().collect::<Vec<_>>());
+        let find = |q: &str| -> Result<Vec<String>> {
+            let ids = query(q)?.results(&file_system)?;
+            Ok(ids.into_iter().map(|id| file_system.path(id)).collect())
+        };
+        let aggregate = |q: &str| -> Result<Option<usize>> {
+            let query = query(q)?;
+            query.aggregate(query.aggregate.unwrap(), &file_system)
+        };
+
+        assert_eq!(
+            aggregate("/ -type d -size -100000 -sum")?,
+            part1(EXAMPLE_INPUT).ok()
+        );
+        assert_eq!(aggregate("-type d -size +8381165 -min")?, Some(24933642));
+        assert_eq!(aggregate("/a -count")?, Some(6));
+        assert_eq!(aggregate("/ -name *.txt -total +0 -max")?, None);
+        assert_eq!(
+            find("/a -type f -sort -size")?,
+            vec!["/a/h.lst", "/a/f", "/a/g", "/a/e/i"]
+        );
+        assert_eq!(
+            find("a -type f -sort -size")?,
+            find("/a -type f -sort -size")?
+        );
+        assert_eq!(find("a/e")?, vec!["/a/e", "/a/e/i"]);
+        assert_eq!(find("/ -ext lst")?, vec!["/a/h.lst"]);
+        assert_eq!(
+            find("/ -depth -1 -type d -sort path")?,
+            vec!["/", "/a", "/d"]
+        );
+        assert_eq!(
+            find("/d -depth +1 -sort name")?,
+            vec!["/d/d.ext", "/d/d.log", "/d/j", "/d/k"]
+        );
+        assert!(find("/b.txt").is_err());
+        assert!(find("/ -size ten").is_err());
+        assert!(find("/ -sort color").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reports() -> Result<()> {
+        let file_system = FileTree::try_from(EXAMPLE_INPUT.parse::<Commands>()?)?;
+        assert_eq!(
+            file_system.tree(&Filter::default()),
+            "- / (dir, size=48381165)
+  - a (dir, size=94853)
+    - e (dir, size=584)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir, size=24933642)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)
+"
+        );
+        let logs = Filter {
+            pattern: Some("*.l*".into()),
+            ..Filter::default()
+        };
+        assert_eq!(
+            file_system.tree(&logs),
+            "- / (dir, size=48381165)
+  - a (dir, size=94853)
+    - h.lst (file, size=62596)
+  - d (dir, size=24933642)
+    - d.log (file, size=8033020)
+"
+        );
+
+        let paths = |ids: Vec<NodeId>| -> Vec<String> {
+            ids.into_iter().map(|id| file_system.path(id)).collect()
+        };
+        let du: Vec<NodeId> = file_system
+            .du(&Filter::default())
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(paths(du), vec!["/", "/d", "/a", "/a/e"]);
+        let small = Filter {
+            max_size: Some(100000),
+            ..Filter::default()
+        };
+        assert_eq!(
+            paths(file_system.largest_directories(5, &small)),
+            vec!["/a", "/a/e"]
+        );
+        assert_eq!(
+            paths(file_system.largest_files(2, &Filter::default())),
+            vec!["/b.txt", "/c.dat"]
+        );
+        assert_eq!(
+            paths(file_system.largest_files(
+                3,
+                &Filter {
+                    min_size: Some(60000),
+                    max_size: Some(5000000),
+                    pattern: None
+                }
+            )),
+            vec!["/d/j", "/a/h.lst"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*.txt", "b.txt"));
+        assert!(!glob_match("*.txt", "b.txt.gz"));
+        assert!(glob_match("d.???", "d.log"));
+        assert!(glob_match("*a*b*", "xxaxxbxx"));
+        assert!(!glob_match("a?", "a"));
+    }
 }
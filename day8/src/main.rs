@@ -1,101 +1,423 @@
-use std::io;
+use std::{
+    env,
+    fmt::{self, Display, Formatter},
+    io,
+};
 
 fn main() {
     let input = io::read_to_string(io::stdin()).unwrap();
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+    let (flags, args): (Vec<String>, Vec<String>) =
+        env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    if args.is_empty() {
+        match part1(&input) {
+            Ok(result) => println!("Part 1: {}", result),
+            Err(err) => eprintln!("Part 1: Error= {}", err),
+        }
+        match part2(&input) {
+            Ok(result) => println!("Part 2: {}", result),
+            Err(err) => eprintln!("Part 2: Error= {}", err),
+        }
+    } else if let Err(err) = report(&input, &args, &flags) {
+        eprintln!("{}: Error= {}", args[0], err);
+    }
 }
 
-fn parse_input(input: &str) -> Vec<Vec<char>> {
-    input
-        .split('\n')
-        .map(|line| line.chars().collect())
-        .collect()
+fn report(input: &str, args: &[String], flags: &[String]) -> Result<(), String> {
+    let grid = parse_input(input).map_err(|err| err.to_string())?;
+    let scores = scenic_scores(&grid);
+    let (score, trees) = best_trees(&scores);
+    let best = trees.first().copied();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["visible", "pgm"] => print!("{}", visibility_pgm(&visibility(&grid))),
+        ["visible"] => print!("{}", render_visibility(&visibility(&grid), best)),
+        ["heat", "ppm"] => print!("{}", heat_map_ppm(&scores, best)),
+        ["heat"] => print!("{}", render_heat_map(&scores, best)),
+        ["best"] => {
+            println!("Best scenic score: {}", score);
+            for (row, col) in trees {
+                println!("row {}, column {}", row, col);
+            }
+        }
+        ["view", row, col] => {
+            let number = |n: &str| n.parse().map_err(|_| format!("{} is not a number", n));
+            let mut viewpoint = Viewpoint {
+                row: number(row)?,
+                col: number(col)?,
+                height: None,
+            };
+            let mut directions = COMPASS.to_vec();
+            for flag in flags {
+                match flag.split_once('=') {
+                    Some(("--height", height)) => {
+                        viewpoint.height = Some(
+                            height
+                                .parse()
+                                .map_err(|_| format!("{} is not a height", height))?,
+                        )
+                    }
+                    None if flag == "--slopes" => directions = slopes(&grid),
+                    _ => return Err(format!("unknown option {}", flag)),
+                }
+            }
+            let trees = visible_from(&grid, viewpoint, &directions)
+                .ok_or("the viewpoint is outside the forest")?;
+            println!("{} trees visible", trees.len());
+            for (row, col) in trees {
+                println!("row {}, column {}: {}", row, col, grid[row][col]);
+            }
+        }
+        _ => return Err("expected visible [pgm], heat [ppm], best or view".into()),
+    }
+    Ok(())
+}
+
+type Height = u8;
+
+#[derive(Debug, PartialEq)]
+enum ParseError {
+    InvalidHeight {
+        line: usize,
+        column: usize,
+        found: char,
+    },
+    Ragged {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
 }
 
-fn part1(input: &str) -> usize {
-    let input = parse_input(input);
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::InvalidHeight {
+                line,
+                column,
+                found,
+            } => write!(
+                f,
+                "line {}, column {}: {:?} is not a tree height",
+                line, column, found
+            ),
+            ParseError::Ragged {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: expected {} trees like the lines before, found {}",
+                line, expected, found
+            ),
+        }
+    }
+}
+
+// Trailing newlines are ignored, CRLF line endings accepted and an empty input is
+// an empty forest. Every line has to be as wide as the first.
+fn parse_input(input: &str) -> Result<Vec<Vec<Height>>, ParseError> {
+    let mut grid: Vec<Vec<Height>> = vec![];
+    for (i, line) in input.trim_end_matches(['\r', '\n']).lines().enumerate() {
+        let row = line
+            .chars()
+            .enumerate()
+            .map(|(col, c)| match c.to_digit(10) {
+                Some(height) => Ok(height as Height),
+                None => Err(ParseError::InvalidHeight {
+                    line: i + 1,
+                    column: col + 1,
+                    found: c,
+                }),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(first) = grid.first().filter(|first| first.len() != row.len()) {
+            return Err(ParseError::Ragged {
+                line: i + 1,
+                expected: first.len(),
+                found: row.len(),
+            });
+        }
+        grid.push(row);
+    }
+    Ok(grid)
+}
 
-    let width = input[0].len();
+fn part1(input: &str) -> Result<usize, ParseError> {
+    let input = parse_input(input)?;
+    Ok(visibility(&input)
+        .into_iter()
+        .flatten()
+        .filter(|&v| v)
+        .count())
+}
+
+// Trees visible from outside the forest, including the whole border. Walls start
+// out as None, which compares lower than any height.
+fn visibility(input: &[Vec<Height>]) -> Vec<Vec<bool>> {
+    let width = input.first().map_or(0, Vec::len);
     let height = input.len();
+    if width == 0 {
+        return vec![vec![]; height];
+    }
 
     let mut visible = vec![vec![false; width]; height];
 
     // Handle visible from left and top
-    let mut dp = vec![vec![('-', '-'); width]; height];
+    let mut dp = vec![vec![(None, None); width]; height];
     for col in 1..width - 1 {
-        dp[0][col] = ('-', input[0][col])
+        dp[0][col] = (None, Some(input[0][col]))
     }
     for row in 1..height - 1 {
-        dp[row][0] = (input[row][0], '-')
+        dp[row][0] = (Some(input[row][0]), None)
     }
 
     for row in 1..height - 1 {
         for col in 1..width - 1 {
             let left_wall = dp[row][col - 1].0;
             let top_wall = dp[row - 1][col].1;
-            let current = input[row][col];
+            let current = Some(input[row][col]);
             visible[row][col] |= current > top_wall || current > left_wall;
             dp[row][col] = (left_wall.max(current), top_wall.max(current))
         }
     }
 
     // Handle visible from right and bottom
-    let mut dp = vec![vec![('-', '-'); width]; height];
+    let mut dp = vec![vec![(None, None); width]; height];
     for col in (1..width - 1).rev() {
-        dp[height - 1][col] = ('-', input[height - 1][col])
+        dp[height - 1][col] = (None, Some(input[height - 1][col]))
     }
     for row in (1..height - 1).rev() {
-        dp[row][width - 1] = (input[row][width - 1], '-')
+        dp[row][width - 1] = (Some(input[row][width - 1]), None)
     }
 
     for row in (1..height - 1).rev() {
         for col in (1..width - 1).rev() {
             let right_wall = dp[row][col + 1].0;
             let bottom_wall = dp[row + 1][col].1;
-            let current = input[row][col];
+            let current = Some(input[row][col]);
             visible[row][col] |= current > bottom_wall || current > right_wall;
             dp[row][col] = (right_wall.max(current), bottom_wall.max(current))
         }
     }
 
-    (width + height - 2) * 2 + visible.into_iter().flatten().filter(|&v| v).count()
+    for (row, line) in visible.iter_mut().enumerate() {
+        for (col, v) in line.iter_mut().enumerate() {
+            *v |= row == 0 || col == 0 || row == height - 1 || col == width - 1;
+        }
+    }
+    visible
 }
 
-fn part2(input: &str) -> usize {
-    let input = parse_input(input);
-    let height = input.len();
-    let width = input[0].len();
+fn part2(input: &str) -> Result<usize, ParseError> {
+    let input = parse_input(input)?;
+    Ok(scenic_scores(&input)
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap_or(0))
+}
+
+// How far each tree sees towards the start of its line. The stack holds the trees
+// not yet hidden behind a taller one, so each tree is pushed and popped once.
+fn viewing_distances<T: PartialOrd>(line: &[T]) -> Vec<usize> {
+    let mut stack: Vec<usize> = vec![];
+    line.iter()
+        .enumerate()
+        .map(|(i, height)| {
+            while stack.last().is_some_and(|&j| line[j] < *height) {
+                stack.pop();
+            }
+            let distance = stack.last().map_or(i, |&j| i - j);
+            stack.push(i);
+            distance
+        })
+        .collect()
+}
 
-    let mut scores = vec![];
+fn scenic_scores<T: PartialOrd + Copy>(grid: &[Vec<T>]) -> Vec<Vec<usize>> {
+    let height = grid.len();
+    let width = grid.first().map_or(0, Vec::len);
+    let mut scores = vec![vec![1; width]; height];
 
-    for row in 1..height - 1 {
-        for col in 1..width - 1 {
-            let current = input[row][col];
-            let left = col
-                - (0..col)
-                    .rev()
-                    .find(|&c| input[row][c] >= current)
-                    .unwrap_or(0);
-            let right = (col + 1..width)
-                .find(|&c| input[row][c] >= current)
-                .unwrap_or(width - 1)
-                - col;
-            let up = row
-                - (0..row)
-                    .rev()
-                    .find(|&r| input[r][col] >= current)
-                    .unwrap_or(0);
-            let down = (row + 1..height)
-                .find(|&r| input[r][col] >= current)
-                .unwrap_or(height - 1)
-                - row;
-
-            let score = up * right * down * left;
-            scores.push(score);
+    for (row, line) in grid.iter().enumerate() {
+        let reversed: Vec<T> = line.iter().rev().copied().collect();
+        let left = viewing_distances(line);
+        let right = viewing_distances(&reversed);
+        for col in 0..width {
+            scores[row][col] *= left[col] * right[width - 1 - col];
+        }
+    }
+    for col in 0..width {
+        let column: Vec<T> = grid.iter().map(|line| line[col]).collect();
+        let reversed: Vec<T> = column.iter().rev().copied().collect();
+        let up = viewing_distances(&column);
+        let down = viewing_distances(&reversed);
+        for row in 0..height {
+            scores[row][col] *= up[row] * down[height - 1 - row];
         }
     }
-    scores.into_iter().max().unwrap()
+    scores
+}
+
+// The best score along with every (row, column) reaching it
+fn best_trees(scores: &[Vec<usize>]) -> (usize, Vec<(usize, usize)>) {
+    let best = scores.iter().flatten().copied().max().unwrap_or(0);
+    let trees = scores
+        .iter()
+        .enumerate()
+        .flat_map(|(row, line)| {
+            line.iter()
+                .enumerate()
+                .filter(move |(_, score)| **score == best)
+                .map(move |(col, _)| (row, col))
+        })
+        .collect();
+    (best, trees)
+}
+
+// A treehouse at (row, col), by default as high as the tree it is built in
+#[derive(Debug, Clone, Copy)]
+struct Viewpoint {
+    row: usize,
+    col: usize,
+    height: Option<Height>,
+}
+
+const COMPASS: [(isize, isize); 8] = [
+    (-1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+];
+
+// Every step in lowest terms that still lands inside the forest, which covers each
+// rational slope a line of sight through the trees can take
+fn slopes(grid: &[Vec<Height>]) -> Vec<(isize, isize)> {
+    let height = grid.len() as isize;
+    let width = grid.first().map_or(0, Vec::len) as isize;
+    (1 - height..height)
+        .flat_map(|dr| (1 - width..width).map(move |dc| (dr, dc)))
+        .filter(|&(dr, dc)| gcd(dr.unsigned_abs(), dc.unsigned_abs()) == 1)
+        .collect()
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
+
+// Walking away from the viewpoint every tree is seen up to the first one at least
+// as tall as the observer, which is seen but hides the rest of the line
+fn line_of_sight(
+    grid: &[Vec<Height>],
+    viewpoint: Viewpoint,
+    (dr, dc): (isize, isize),
+) -> Vec<(usize, usize)> {
+    let eye = viewpoint
+        .height
+        .unwrap_or(grid[viewpoint.row][viewpoint.col]);
+    let mut seen = vec![];
+    let (mut row, mut col) = (viewpoint.row, viewpoint.col);
+    while let (Some(r), Some(c)) = (
+        row.checked_add_signed(dr).filter(|r| *r < grid.len()),
+        col.checked_add_signed(dc).filter(|c| *c < grid[0].len()),
+    ) {
+        (row, col) = (r, c);
+        seen.push((row, col));
+        if grid[row][col] >= eye {
+            break;
+        }
+    }
+    seen
+}
+
+fn visible_from(
+    grid: &[Vec<Height>],
+    viewpoint: Viewpoint,
+    directions: &[(isize, isize)],
+) -> Option<Vec<(usize, usize)>> {
+    grid.get(viewpoint.row)?.get(viewpoint.col)?;
+    let mut trees: Vec<(usize, usize)> = directions
+        .iter()
+        .flat_map(|direction| line_of_sight(grid, viewpoint, *direction))
+        .collect();
+    trees.sort();
+    trees.dedup();
+    Some(trees)
+}
+
+const HEAT_RAMP: &[u8] = b" .:-=+*#%@";
+const BEST_TREE: char = 'X';
+
+fn render_visibility(visible: &[Vec<bool>], best: Option<(usize, usize)>) -> String {
+    render_ascii(visible, best, |&v| if v { '#' } else { '.' })
+}
+
+// Any tree with a view at all gets at least the second character of the ramp
+fn render_heat_map(scores: &[Vec<usize>], best: Option<(usize, usize)>) -> String {
+    let max = scores.iter().flatten().copied().max().unwrap_or(0).max(1);
+    let steps = HEAT_RAMP.len() - 1;
+    render_ascii(scores, best, |&score| {
+        HEAT_RAMP[(score * steps).div_ceil(max)] as char
+    })
+}
+
+fn render_ascii<T>(
+    grid: &[Vec<T>],
+    best: Option<(usize, usize)>,
+    symbol: impl Fn(&T) -> char,
+) -> String {
+    let mut output = String::new();
+    for (row, line) in grid.iter().enumerate() {
+        for (col, cell) in line.iter().enumerate() {
+            match best == Some((row, col)) {
+                true => output.push(BEST_TREE),
+                false => output.push(symbol(cell)),
+            }
+        }
+        output.push('\n');
+    }
+    output
+}
+
+// Plain (P2) graymap with visible trees in white
+fn visibility_pgm(visible: &[Vec<bool>]) -> String {
+    let width = visible.first().map_or(0, Vec::len);
+    let mut output = format!("P2\n{} {}\n255\n", width, visible.len());
+    for line in visible {
+        let pixels: Vec<&str> = line.iter().map(|&v| if v { "255" } else { "0" }).collect();
+        output.push_str(&pixels.join(" "));
+        output.push('\n');
+    }
+    output
+}
+
+// Plain (P3) pixmap with scores as shades of gray and the best tree in red
+fn heat_map_ppm(scores: &[Vec<usize>], best: Option<(usize, usize)>) -> String {
+    let width = scores.first().map_or(0, Vec::len);
+    let max = scores.iter().flatten().copied().max().unwrap_or(0).max(1);
+    let mut output = format!("P3\n{} {}\n255\n", width, scores.len());
+    for (row, line) in scores.iter().enumerate() {
+        let pixels: Vec<String> = line
+            .iter()
+            .enumerate()
+            .map(|(col, score)| match best == Some((row, col)) {
+                true => "255 0 0".to_string(),
+                false => {
+                    let level = score * 255 / max;
+                    format!("{} {} {}", level, level, level)
+                }
+            })
+            .collect();
+        output.push_str(&pixels.join(" "));
+        output.push('\n');
+    }
+    output
 }
 
 #[cfg(test)]
@@ -109,11 +431,126 @@ mod tests {
 
     #[test]
     fn test_part_1_example() {
-        assert_eq!(part1(EXAMPLE_INPUT), 21);
+        assert_eq!(part1(EXAMPLE_INPUT), Ok(21));
     }
 
     #[test]
     fn test_part_2_example() {
-        assert_eq!(part2(EXAMPLE_INPUT), 8);
+        assert_eq!(part2(EXAMPLE_INPUT), Ok(8));
+    }
+
+    #[test]
+    fn test_parse_input() {
+        let crlf = EXAMPLE_INPUT.replace('\n', "\r\n") + "\r\n";
+        assert_eq!(parse_input(&crlf), parse_input(EXAMPLE_INPUT));
+        assert_eq!(part1(&(EXAMPLE_INPUT.to_string() + "\n\n")), Ok(21));
+        assert_eq!(parse_input("12\n30"), Ok(vec![vec![1, 2], vec![3, 0]]));
+        assert_eq!(
+            parse_input("123\n4a6"),
+            Err(ParseError::InvalidHeight {
+                line: 2,
+                column: 2,
+                found: 'a'
+            })
+        );
+        assert_eq!(
+            parse_input("123\n45\n789"),
+            Err(ParseError::Ragged {
+                line: 2,
+                expected: 3,
+                found: 2
+            })
+        );
+        assert!(parse_input("12\n\n34").is_err());
+
+        // every tree of a forest this thin is on the border
+        assert_eq!((part1(""), part2("")), (Ok(0), Ok(0)));
+        assert_eq!((part1("5"), part2("5")), (Ok(1), Ok(0)));
+        assert_eq!((part1("3141"), part2("3141")), (Ok(4), Ok(0)));
+        assert_eq!((part1("3\n1\n4\n\n"), part2("3\n1\n4")), (Ok(3), Ok(0)));
+        assert_eq!((part1("12\n34"), part2("12\n34")), (Ok(4), Ok(0)));
+    }
+
+    #[test]
+    fn test_scenic_scores() {
+        let scores = scenic_scores(&parse_input(EXAMPLE_INPUT).unwrap());
+        assert_eq!(
+            scores,
+            vec![
+                vec![0, 0, 0, 0, 0],
+                vec![0, 1, 4, 1, 0],
+                vec![0, 6, 1, 2, 0],
+                vec![0, 1, 8, 3, 0],
+                vec![0, 0, 0, 0, 0],
+            ]
+        );
+        assert_eq!(best_trees(&scores), (8, vec![(3, 2)]));
+
+        let ties = scenic_scores(&parse_input("000\n010\n000").unwrap());
+        assert_eq!(best_trees(&ties), (1, vec![(1, 1)]));
+        let flat = scenic_scores(&parse_input("11\n11").unwrap());
+        assert_eq!(best_trees(&flat), (0, vec![(0, 0), (0, 1), (1, 0), (1, 1)]));
+    }
+
+    #[test]
+    fn test_line_of_sight() {
+        let grid = parse_input(EXAMPLE_INPUT).unwrap();
+        let treehouse = Viewpoint {
+            row: 3,
+            col: 2,
+            height: None,
+        };
+        // along the axes this is exactly the scenic score's 2 * 2 * 1 * 2
+        let axes = [(-1, 0), (0, -1), (1, 0), (0, 1)];
+        assert_eq!(
+            visible_from(&grid, treehouse, &axes),
+            Some(vec![(1, 2), (2, 2), (3, 0), (3, 1), (3, 3), (3, 4), (4, 2)])
+        );
+        assert_eq!(line_of_sight(&grid, treehouse, (-1, -1)), vec![(2, 1)]);
+        assert_eq!(
+            line_of_sight(&grid, treehouse, (-1, 1)),
+            vec![(2, 3), (1, 4)]
+        );
+        assert_eq!(visible_from(&grid, treehouse, &COMPASS).unwrap().len(), 12);
+
+        // slopes between the compass directions skip over the trees in between
+        assert_eq!(line_of_sight(&grid, treehouse, (-1, 2)), vec![(2, 4)]);
+        assert_eq!(line_of_sight(&grid, treehouse, (-2, -1)), vec![(1, 1)]);
+
+        // high enough up every tree in the forest comes into view
+        let tower = Viewpoint {
+            height: Some(10),
+            ..treehouse
+        };
+        assert_eq!(
+            visible_from(&grid, tower, &slopes(&grid)).unwrap().len(),
+            24
+        );
+        let outside = Viewpoint {
+            row: 5,
+            ..treehouse
+        };
+        assert_eq!(visible_from(&grid, outside, &COMPASS), None);
+    }
+
+    #[test]
+    fn test_renderers() {
+        let grid = parse_input(EXAMPLE_INPUT).unwrap();
+        let visible = visibility(&grid);
+        let scores = scenic_scores(&grid);
+        let best = Some((3, 2));
+        assert_eq!(
+            render_visibility(&visible, best),
+            "#####\n###.#\n##.##\n#.X.#\n#####\n"
+        );
+        assert_eq!(
+            render_heat_map(&scores, best),
+            "     \n :+: \n #:- \n :X= \n     \n"
+        );
+        assert!(visibility_pgm(&visible)
+            .starts_with("P2\n5 5\n255\n255 255 255 255 255\n255 255 255 0 255\n"));
+        let ppm = heat_map_ppm(&scores, best);
+        assert!(ppm.starts_with("P3\n5 5\n255\n0 0 0 "));
+        assert!(ppm.contains("0 0 0 31 31 31 255 0 0 95 95 95 0 0 0\n"));
     }
 }
@@ -1,21 +1,100 @@
 use std::{
     collections::HashSet,
-    fmt::Debug,
-    io,
+    env,
+    fmt::{self, Debug, Display, Formatter},
+    io, iter,
     ops::{AddAssign, Sub},
     str::FromStr,
 };
 
 fn main() {
     let input = io::read_to_string(io::stdin()).unwrap();
-    println!("Part 1: {}", part1(&input));
-    println!("Part 2: {}", part2(&input));
+    let (flags, args): (Vec<String>, Vec<String>) =
+        env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    let mut length = 10;
+    let mut rule = FollowRule::Chebyshev;
+    for flag in &flags {
+        let parsed = match flag.split_once('=') {
+            Some(("--length", value)) => value.parse().map(|value| length = value).ok(),
+            Some(("--rule", value)) => value.parse().map(|value| rule = value).ok(),
+            _ => None,
+        };
+        if parsed.is_none() {
+            eprintln!("Error= invalid option {}", flag);
+            return;
+        }
+    }
+    if args.is_empty() && flags.is_empty() {
+        match part1(&input) {
+            Ok(result) => println!("Part 1: {}", result),
+            Err(err) => eprintln!("Part 1: Error= {}", err),
+        }
+        match part2(&input) {
+            Ok(result) => println!("Part 2: {}", result),
+            Err(err) => eprintln!("Part 2: Error= {}", err),
+        }
+        return;
+    }
+    let steps = match parse_input(&input) {
+        Ok(steps) => steps,
+        Err(err) => {
+            eprintln!("Error= {}", err);
+            return;
+        }
+    };
+    let rope = Rope::new(length, rule);
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match &args[..] {
+        ["frames", ref mode @ ..] => print!("{}", animate(&steps, rope, mode == ["step"])),
+        ["positions"] => {
+            for knots in simulate(directions(&steps), rope) {
+                let knots: Vec<String> = knots
+                    .iter()
+                    .map(|knot| format!("{},{}", knot.0, knot.1))
+                    .collect();
+                println!("{}", knots.join(" "));
+            }
+        }
+        [] => {
+            let rope = run(directions(&steps), rope);
+            for knot in 0..length {
+                println!("Knot {}: {} positions", knot, rope.visited(knot).len());
+            }
+        }
+        _ => eprintln!("Error= expected frames [step] or positions"),
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 enum Error {
-    InvalidDirection,
+    InvalidDirection(String),
+    InvalidCount(String),
     InvalidMove,
+    UnknownRule,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidDirection(direction) => write!(f, "{:?} is not a direction", direction),
+            Error::InvalidCount(count) => write!(f, "{:?} is not a number of steps", count),
+            Error::InvalidMove => write!(f, "expected a direction and a number of steps"),
+            Error::UnknownRule => write!(f, "unknown follow rule"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct LineError {
+    line: usize,
+    text: String,
+    error: Error,
+}
+
+impl Display for LineError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {} in {:?}", self.line, self.error, self.text)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -26,6 +105,10 @@ impl Vector2 {
         self.0.abs().max(self.1.abs())
     }
 
+    fn manhattan(&self) -> i64 {
+        self.0.abs() + self.1.abs()
+    }
+
     fn clamp(&self, min: &Vector2, max: &Vector2) -> Vector2 {
         Vector2(self.0.clamp(min.0, max.0), self.1.clamp(min.1, max.1))
     }
@@ -46,7 +129,7 @@ impl Sub for Vector2 {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 struct Direction(Vector2);
 
 impl FromStr for Direction {
@@ -57,65 +140,306 @@ impl FromStr for Direction {
             "R" => Ok(Direction(Vector2(1, 0))),
             "D" => Ok(Direction(Vector2(0, -1))),
             "L" => Ok(Direction(Vector2(-1, 0))),
-            _ => Err(Error::InvalidDirection),
+            "UR" => Ok(Direction(Vector2(1, 1))),
+            "DR" => Ok(Direction(Vector2(1, -1))),
+            "DL" => Ok(Direction(Vector2(-1, -1))),
+            "UL" => Ok(Direction(Vector2(-1, 1))),
+            _ => Err(Error::InvalidDirection(s.to_string())),
         }
     }
 }
 
-struct Steps(Vec<Direction>);
+impl Display for Direction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self.0 {
+            Vector2(0, 1) => "U",
+            Vector2(1, 0) => "R",
+            Vector2(0, -1) => "D",
+            Vector2(-1, 0) => "L",
+            Vector2(1, 1) => "UR",
+            Vector2(1, -1) => "DR",
+            Vector2(-1, -1) => "DL",
+            Vector2(-1, 1) => "UL",
+            _ => "?",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// One line of the input, kept as a count so long moves never take up memory
+struct Steps {
+    direction: Direction,
+    count: usize,
+}
 
 impl FromStr for Steps {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (direction, spaces) = s.split_once(' ').ok_or(Error::InvalidMove)?;
-        let direction: Direction = direction.parse()?;
-        let spaces: usize = spaces.parse().or(Err(Error::InvalidMove))?;
-        Ok(Steps(vec![direction; spaces]))
+        let mut parts = s.split_whitespace();
+        let (Some(direction), Some(count), None) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(Error::InvalidMove);
+        };
+        Ok(Steps {
+            direction: direction.parse()?,
+            count: count
+                .parse()
+                .map_err(|_| Error::InvalidCount(count.to_string()))?,
+        })
     }
 }
 
-fn parse_input(input: &str) -> Vec<Direction> {
+impl Display for Steps {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.direction, self.count)
+    }
+}
+
+// Blank lines are skipped, and the first bad line is reported with its number
+fn parse_input(input: &str) -> Result<Vec<Steps>, LineError> {
     input
         .lines()
-        .map(str::parse::<Steps>)
-        .map(Result::unwrap)
-        .flat_map(|s| s.0)
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            line.parse().map_err(|error| LineError {
+                line: i + 1,
+                text: line.to_string(),
+                error,
+            })
+        })
         .collect()
 }
 
-fn count_tail_positions<const LENGTH: usize>(moves: Vec<Direction>) -> usize {
-    let mut knots = [Vector2(0, 0); LENGTH];
+fn directions(steps: &[Steps]) -> impl Iterator<Item = Direction> + '_ {
+    steps
+        .iter()
+        .flat_map(|steps| iter::repeat_n(steps.direction, steps.count))
+}
 
-    let mut tail_positions = HashSet::new();
-    tail_positions.insert(knots[LENGTH - 1]);
+// How a knot catches up with the one ahead of it once they stop touching. Knots
+// keep stepping until they touch again, so a diagonal move of the head never
+// leaves them behind.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FollowRule {
+    // touching includes diagonals, and knots step diagonally to catch up
+    Chebyshev,
+    // only knots side by side touch, and knots step like a rook along the
+    // axis with the larger gap, horizontally on ties
+    Manhattan,
+    // the rope gives until knots are more than the slack apart, then they
+    // follow like Chebyshev knots, which are the stretchy ones with a slack of 1
+    Stretchy(i64),
+}
 
-    for direction in moves {
-        let (head, rest) = knots.split_at_mut(1);
-        let mut prior = &mut head[0];
-        *prior += direction.0;
+impl FollowRule {
+    fn follow(&self, mut knot: Vector2, prior: Vector2) -> Vector2 {
+        loop {
+            let delta = prior - knot;
+            let (touching, step) = match *self {
+                FollowRule::Chebyshev => (
+                    delta.max_magnitude() <= 1,
+                    delta.clamp(&Vector2(-1, -1), &Vector2(1, 1)),
+                ),
+                FollowRule::Stretchy(slack) => (
+                    delta.max_magnitude() <= slack,
+                    delta.clamp(&Vector2(-1, -1), &Vector2(1, 1)),
+                ),
+                FollowRule::Manhattan => (
+                    delta.manhattan() <= 1,
+                    match delta.0.abs() >= delta.1.abs() {
+                        true => Vector2(delta.0.signum(), 0),
+                        false => Vector2(0, delta.1.signum()),
+                    },
+                ),
+            };
+            if touching {
+                return knot;
+            }
+            knot += step;
+        }
+    }
+}
+
+impl FromStr for FollowRule {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "chebyshev" => Ok(FollowRule::Chebyshev),
+            None if s == "manhattan" => Ok(FollowRule::Manhattan),
+            Some(("stretchy", slack)) => match slack.parse() {
+                Ok(slack) if slack >= 0 => Ok(FollowRule::Stretchy(slack)),
+                _ => Err(Error::UnknownRule),
+            },
+            _ => Err(Error::UnknownRule),
+        }
+    }
+}
+
+// The knots from head to tail along with every position each of them has been in
+#[derive(Clone)]
+struct Rope {
+    knots: Vec<Vector2>,
+    visited: Vec<HashSet<Vector2>>,
+    rule: FollowRule,
+}
+
+impl Rope {
+    fn new(length: usize, rule: FollowRule) -> Self {
+        Self {
+            knots: vec![Vector2(0, 0); length],
+            visited: vec![HashSet::from([Vector2(0, 0)]); length],
+            rule,
+        }
+    }
+
+    fn step(&mut self, direction: &Direction) {
+        let Some((head, rest)) = self.knots.split_first_mut() else {
+            return;
+        };
+        *head += direction.0;
+        let mut prior = head;
 
         for knot in rest.iter_mut() {
-            let delta = *prior - *knot;
-            if delta.max_magnitude() > 1 {
-                *knot += delta.clamp(&Vector2(-1, -1), &Vector2(1, 1));
-            }
+            *knot = self.rule.follow(*knot, *prior);
             prior = knot;
         }
 
-        tail_positions.insert(knots[LENGTH - 1]);
+        for (knot, visited) in self.knots.iter().zip(self.visited.iter_mut()) {
+            visited.insert(*knot);
+        }
+    }
+
+    fn knots(&self) -> &[Vector2] {
+        &self.knots
+    }
+
+    fn visited(&self, knot: usize) -> &HashSet<Vector2> {
+        &self.visited[knot]
+    }
+}
+
+// Moves the rope one step at a time, yielding where every knot is afterwards
+struct Simulation<I> {
+    rope: Rope,
+    moves: I,
+}
+
+impl<I: Iterator<Item = Direction>> Iterator for Simulation<I> {
+    type Item = Vec<Vector2>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let direction = self.moves.next()?;
+        self.rope.step(&direction);
+        Some(self.rope.knots().to_vec())
+    }
+}
+
+fn simulate<I: IntoIterator<Item = Direction>>(moves: I, rope: Rope) -> Simulation<I::IntoIter> {
+    Simulation {
+        rope,
+        moves: moves.into_iter(),
+    }
+}
+
+fn run(moves: impl IntoIterator<Item = Direction>, rope: Rope) -> Rope {
+    let mut simulation = simulate(moves, rope);
+    simulation.by_ref().for_each(drop);
+    simulation.rope
+}
+
+// The smallest box holding every given position, drawn with up at the top
+struct Viewport {
+    min: Vector2,
+    max: Vector2,
+}
+
+impl Viewport {
+    fn fit<'a>(positions: impl IntoIterator<Item = &'a Vector2>) -> Self {
+        let mut viewport = Viewport {
+            min: Vector2(0, 0),
+            max: Vector2(0, 0),
+        };
+        for position in positions {
+            viewport.min = Vector2(
+                viewport.min.0.min(position.0),
+                viewport.min.1.min(position.1),
+            );
+            viewport.max = Vector2(
+                viewport.max.0.max(position.0),
+                viewport.max.1.max(position.1),
+            );
+        }
+        viewport
+    }
+}
+
+// Drawn in the puzzle's notation: the head as H, the knots by their number (or T
+// for the tail of a two knot rope), s for the start and # where the tail has been.
+// Knots closer to the head cover the ones behind them.
+fn render(rope: &Rope, viewport: &Viewport) -> String {
+    let knots = rope.knots();
+    let tail_visited = match knots.len() {
+        0 => None,
+        length => Some(rope.visited(length - 1)),
+    };
+    let mut output = String::new();
+    for y in (viewport.min.1..=viewport.max.1).rev() {
+        for x in viewport.min.0..=viewport.max.0 {
+            let position = Vector2(x, y);
+            let symbol = match knots.iter().position(|knot| *knot == position) {
+                Some(0) => 'H',
+                Some(1) if knots.len() == 2 => 'T',
+                Some(i) => char::from_digit(i as u32, 36).unwrap_or('*'),
+                None if position == Vector2(0, 0) => 's',
+                None if tail_visited.is_some_and(|visited| visited.contains(&position)) => '#',
+                None => '.',
+            };
+            output.push(symbol);
+        }
+        output.push('\n');
     }
+    output
+}
 
-    tail_positions.len()
+// A frame after every line of the input, or after every single step, all sharing
+// the viewport that fits the whole simulation
+fn animate(steps: &[Steps], mut rope: Rope, every_step: bool) -> String {
+    let whole = run(directions(steps), rope.clone());
+    let viewport = Viewport::fit(whole.visited.iter().flatten());
+
+    let mut output = format!("== Initial State ==\n\n{}\n", render(&rope, &viewport));
+    for line in steps {
+        output.push_str(&format!("== {} ==\n\n", line));
+        for _ in 0..line.count {
+            rope.step(&line.direction);
+            if every_step {
+                output.push_str(&format!("{}\n", render(&rope, &viewport)));
+            }
+        }
+        if !every_step {
+            output.push_str(&format!("{}\n", render(&rope, &viewport)));
+        }
+    }
+    output
 }
 
-fn part1(input: &str) -> usize {
-    let moves = parse_input(input);
-    count_tail_positions::<2>(moves)
+fn count_tail_positions(steps: &[Steps], length: usize) -> usize {
+    match length {
+        0 => 0,
+        _ => run(directions(steps), Rope::new(length, FollowRule::Chebyshev))
+            .visited(length - 1)
+            .len(),
+    }
 }
 
-fn part2(input: &str) -> usize {
-    let moves = parse_input(input);
-    count_tail_positions::<10>(moves)
+fn part1(input: &str) -> Result<usize, LineError> {
+    let steps = parse_input(input)?;
+    Ok(count_tail_positions(&steps, 2))
+}
+
+fn part2(input: &str) -> Result<usize, LineError> {
+    let steps = parse_input(input)?;
+    Ok(count_tail_positions(&steps, 10))
 }
 
 #[cfg(test)]
@@ -142,12 +466,141 @@ U 20";
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(EXAMPLE_INPUT), 13);
+        assert_eq!(part1(EXAMPLE_INPUT), Ok(13));
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(EXAMPLE_INPUT), 1);
-        assert_eq!(part2(LARGER_EXAMPLE_INPUT), 36);
+        assert_eq!(part2(EXAMPLE_INPUT), Ok(1));
+        assert_eq!(part2(LARGER_EXAMPLE_INPUT), Ok(36));
+    }
+
+    #[test]
+    fn test_rope() {
+        let example = parse_input(EXAMPLE_INPUT).unwrap();
+        let rope = run(directions(&example), Rope::new(3, FollowRule::Chebyshev));
+        let counts: Vec<usize> = (0..3).map(|knot| rope.visited(knot).len()).collect();
+        assert_eq!(counts, vec![21, 13, 7]);
+        assert_eq!(rope.knots(), &[Vector2(2, 2), Vector2(1, 2), Vector2(2, 2)]);
+        assert_eq!(count_tail_positions(&example, 1), 21);
+        assert_eq!(count_tail_positions(&example, 0), 0);
+
+        let moves = parse_input("R 2\nU 2").unwrap();
+        let mut steps = simulate(directions(&moves), Rope::new(2, FollowRule::Chebyshev));
+        assert_eq!(steps.next(), Some(vec![Vector2(1, 0), Vector2(0, 0)]));
+        assert_eq!(steps.next(), Some(vec![Vector2(2, 0), Vector2(1, 0)]));
+        assert_eq!(steps.next(), Some(vec![Vector2(2, 1), Vector2(1, 0)]));
+        assert_eq!(steps.next(), Some(vec![Vector2(2, 2), Vector2(2, 1)]));
+        assert_eq!(steps.next(), None);
+    }
+
+    #[test]
+    fn test_follow_rules() {
+        let tail = |input: &str, rule| {
+            let steps = parse_input(input).unwrap();
+            let rope = run(directions(&steps), Rope::new(2, rule));
+            (rope.knots()[1], rope.visited(1).len())
+        };
+        assert_eq!(tail("UR 3", FollowRule::Chebyshev), (Vector2(2, 2), 3));
+        assert_eq!(tail("R 1\nU 1", FollowRule::Chebyshev), (Vector2(0, 0), 1));
+        // a diagonal neighbour is too far for a manhattan rope, so the tail
+        // steps sideways to sit right below the head
+        assert_eq!(tail("R 1\nU 1", FollowRule::Manhattan), (Vector2(1, 0), 2));
+        assert_eq!(tail("UR 2", FollowRule::Manhattan), (Vector2(2, 1), 3));
+        assert_eq!(tail("R 3", FollowRule::Stretchy(2)), (Vector2(1, 0), 2));
+        assert_eq!(
+            tail("R 2\nDL 1", FollowRule::Stretchy(0)),
+            (Vector2(1, -1), 4)
+        );
+        let example = parse_input(EXAMPLE_INPUT).unwrap();
+        assert_eq!(
+            count_tail_positions(&example, 2),
+            run(directions(&example), Rope::new(2, FollowRule::Stretchy(1)))
+                .visited(1)
+                .len()
+        );
+        assert_eq!(
+            "stretchy:3".parse::<FollowRule>().ok(),
+            Some(FollowRule::Stretchy(3))
+        );
+        assert!("stretchy:-1".parse::<FollowRule>().is_err());
+        assert!("UU 1".parse::<Steps>().is_err());
+        assert_eq!("DL 4".parse::<Steps>().unwrap().to_string(), "DL 4");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |line, text: &str, error| LineError {
+            line,
+            text: text.to_string(),
+            error,
+        };
+        assert_eq!(
+            part1("R 4\nX 2").err(),
+            Some(error(2, "X 2", Error::InvalidDirection("X".into())))
+        );
+        assert_eq!(
+            parse_input("U 1\n\nL -3").err(),
+            Some(error(3, "L -3", Error::InvalidCount("-3".into())))
+        );
+        assert_eq!(
+            parse_input("R").err(),
+            Some(error(1, "R", Error::InvalidMove))
+        );
+        assert_eq!(
+            parse_input("R 1 2").err().map(|err| err.to_string()),
+            Some("line 1: expected a direction and a number of steps in \"R 1 2\"".into())
+        );
+        assert_eq!(part1("R 4\r\nU 4\r\n\r\n"), Ok(7));
+
+        // a long move is only ever walked one step at a time
+        let steps = parse_input("R 1000000000\nU 3").unwrap();
+        assert_eq!(steps[0].count, 1000000000);
+        assert_eq!(
+            directions(&steps).nth(1000000001).map(|d| d.0),
+            Some(Vector2(0, 1))
+        );
+    }
+
+    #[test]
+    fn test_frames() {
+        let steps = parse_input("R 4\nU 4").unwrap();
+        assert_eq!(
+            animate(&steps, Rope::new(2, FollowRule::Chebyshev), false),
+            "== Initial State ==
+
+.....
+.....
+.....
+.....
+H....
+
+== R 4 ==
+
+.....
+.....
+.....
+.....
+s##TH
+
+== U 4 ==
+
+....H
+....T
+....#
+....#
+s###.
+
+"
+        );
+        let frames = animate(
+            &parse_input("L 2\nD 1").unwrap(),
+            Rope::new(3, FollowRule::Chebyshev),
+            true,
+        );
+        assert_eq!(
+            frames,
+            "== Initial State ==\n\n..H\n...\n\n== L 2 ==\n\n.H1\n...\n\nH12\n...\n\n== D 1 ==\n\n.12\nH..\n\n"
+        );
     }
 }